@@ -0,0 +1,103 @@
+//! Build RocksDB from a sorted source with one SST file per prefix shard.
+//!
+//! Usage:
+//! ```
+//! cargo run --example build-from-sorted -- --db-dir data.rocksdb
+//! ```
+//!
+//! This will generate sorted hex keys for each 3-hex-char prefix shard (the prefix followed by a consecutive hex
+//! suffix, KEY_LEN in total) with random hex values, and write each shard to its own SST file with SstFileWriter.
+//! Shards are built in parallel by rayon's default thread pool (RAYON_NUM_THREADS), in `<db-dir>.sst` by default.
+//! All files are then ingested into the DB in one call. Since the shards don't overlap, ingestion only links
+//! the files into the bottommost level, which makes this the fastest load path.
+//! Keys within each shard must be sorted for SstFileWriter, which the generator guarantees.
+
+use anyhow::Result;
+use clap::Parser;
+use rayon::prelude::*;
+use rocksdb_examples::rocksdb_utils::{BulkOpenOptions, open_rocksdb_for_bulk_ingestion};
+use rocksdb_examples::utils::{
+    generate_consecutive_hex_strings, generate_random_hex_string, make_progress_bar,
+};
+use rust_rocksdb::{IngestExternalFileOptions, Options, SstFileWriter};
+use std::time::Instant;
+
+const ENTRIES_PER_SHARD: usize = 200;
+const PREFIX_LEN: u32 = 3;
+const KEY_LEN: usize = 16;
+const VAL_LEN: usize = 3;
+const ROCKSDB_NUM_LEVELS: i32 = 7;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Directory for the intermediate SST files (defaults to <db-dir>.sst)
+    #[arg(long)]
+    sst_dir: Option<String>,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let sst_dir = args
+        .sst_dir
+        .clone()
+        .unwrap_or_else(|| format!("{}.sst", args.db_dir));
+    std::fs::create_dir_all(&sst_dir)?;
+
+    let prefixes = generate_consecutive_hex_strings(PREFIX_LEN);
+    let pb = make_progress_bar(Some(prefixes.len() as u64));
+
+    let start = Instant::now();
+    let sst_paths = prefixes
+        .into_par_iter()
+        .map(|prefix| -> Result<String> {
+            // ingested files land in the bottommost level, so use its compression
+            let mut opts = Options::default();
+            opts.set_compression_type(rust_rocksdb::DBCompressionType::Zstd);
+            let mut writer = SstFileWriter::create(&opts);
+            let sst_path = format!("{}/{}.sst", sst_dir, prefix);
+            writer.open(&sst_path)?;
+
+            let suffix_len = KEY_LEN - prefix.len();
+            for i in 0..ENTRIES_PER_SHARD {
+                let key = format!("{prefix}{i:0suffix_len$x}");
+                let val = generate_random_hex_string(VAL_LEN);
+                writer.put(key.as_bytes(), val.as_bytes())?;
+            }
+            writer.finish()?;
+            pb.inc(1);
+            Ok(sst_path)
+        })
+        .collect::<Result<Vec<String>>>()?;
+    pb.finish_with_message("done");
+    println!(
+        "Built {} SST files in {} in {:.2?}",
+        sst_paths.len(),
+        sst_dir,
+        start.elapsed()
+    );
+
+    let db = open_rocksdb_for_bulk_ingestion(
+        &args.db_dir,
+        BulkOpenOptions {
+            num_levels: Some(ROCKSDB_NUM_LEVELS),
+            ..Default::default()
+        },
+    )?;
+    let mut ingest_opts = IngestExternalFileOptions::default();
+    ingest_opts.set_move_files(true);
+
+    let start = Instant::now();
+    db.ingest_external_file_opts(&ingest_opts, sst_paths)?;
+    println!(
+        "Ingested {} entries into {} in {:.2?}",
+        ENTRIES_PER_SHARD * 16_usize.pow(PREFIX_LEN),
+        args.db_dir,
+        start.elapsed()
+    );
+
+    std::fs::remove_dir_all(&sst_dir)?;
+    Ok(())
+}
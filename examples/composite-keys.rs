@@ -0,0 +1,70 @@
+//! Write and range-scan multi-field (composite) keys.
+//!
+//! Usage:
+//! ```
+//! cargo run --example composite-keys -- --db-dir data.rocksdb
+//! cargo run --example composite-keys -- --db-dir data.rocksdb --user user-0003
+//! ```
+//!
+//! This will write NUM_EVENTS events for each of NUM_USERS users, keyed by (user id, timestamp) with
+//! `encode_composite_key`, then scan all events of one user in timestamp order by seeking to the encoded user id.
+//! Timestamps are big-endian u64, so they sort numerically; values are random hex strings.
+//! Since every field is terminated, scanning "user-1" never picks up keys of "user-10".
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{WriteOpenOptions, open_rocksdb_for_write};
+use rocksdb_examples::utils::{
+    decode_composite_key, encode_composite_key, generate_random_hex_string,
+};
+use rust_rocksdb::{Direction, IteratorMode, WriteBatch};
+
+const NUM_USERS: usize = 16;
+const NUM_EVENTS: u64 = 10;
+const VAL_LEN: usize = 3;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// User id whose events to scan
+    #[arg(long, default_value = "user-0000")]
+    user: String,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db = open_rocksdb_for_write(&args.db_dir, WriteOpenOptions::default())?;
+
+    let mut batch = WriteBatch::default();
+    for user in 0..NUM_USERS {
+        let user_id = format!("user-{user:04}");
+        // write timestamps out of order, the encoding sorts them
+        for ts in (0..NUM_EVENTS).rev() {
+            let key = encode_composite_key(&[user_id.as_bytes(), &(ts * 1000).to_be_bytes()]);
+            batch.put(key, generate_random_hex_string(VAL_LEN).as_bytes());
+        }
+    }
+    db.write(&batch)?;
+
+    let prefix = encode_composite_key(&[args.user.as_bytes()]);
+    let mut count = 0;
+    for item in db.iterator(IteratorMode::From(&prefix, Direction::Forward)) {
+        let (key, value) = item?;
+        if !key.starts_with(&prefix) {
+            break;
+        }
+        let parts = decode_composite_key(&key)?;
+        let ts = u64::from_be_bytes(parts[1].as_slice().try_into()?);
+        println!(
+            "user: {} ts: {} val: {}",
+            String::from_utf8_lossy(&parts[0]),
+            ts,
+            String::from_utf8_lossy(&value)
+        );
+        count += 1;
+    }
+    println!("Events for {}: {}", args.user, count);
+    Ok(())
+}
@@ -0,0 +1,185 @@
+//! Delete a list of hex keys from RocksDB.
+//!
+//! Usage:
+//! ```
+//! cargo run --example delete-keys -- --db-dir data.rocksdb --keys-file keys.txt
+//! cargo run --example delete-keys -- --db-dir data.rocksdb --keys-file keys.txt --deletion-window 1000 --deletion-count 500
+//! cargo run --example delete-keys -- --db-dir data.rocksdb --keys-file keys.txt --dry-run
+//! ```
+//!
+//! This will stream the keys file (one hex key per line, blank lines skipped) and delete the keys in WriteBatch chunks
+//! of BATCH_SIZE, then compact the DB to drop the tombstones.
+//! Before deleting, each key is checked with `key_may_exist` and confirmed with a point read,
+//! so the report of how many requested keys actually existed is exact.
+//! With `--deletion-window` and `--deletion-count`, the DB is opened with a compact-on-deletion collector instead:
+//! flushed files dense with tombstones are marked for compaction and RocksDB reclaims their space in the background
+//! whenever the DB is open for writing, so the manual compaction is skipped. The tombstones are flushed right away,
+//! since the collector only sees them in SST files, then the live SST size is printed before and after the
+//! background compactions settle (no compaction running or pending, polled every COMPACTION_POLL_INTERVAL for up to
+//! COMPACTION_WAIT_TIMEOUT), to show the space they reclaimed.
+//! With `--dry-run`, the DB is opened read-only and the keys are checked the same way, but nothing is deleted or
+//! compacted: it reports how many keys would be deleted, with up to SAMPLE_SIZE of them. On unchanged data and a keys
+//! file without duplicates, the counts match those of a real run exactly. A key listed twice counts as existing both
+//! times in a dry run, but only once in a real run if its first deletion was written in an earlier batch.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, WriteOpenOptions, open_rocksdb_for_read_only, open_rocksdb_for_write,
+};
+use rocksdb_examples::utils::make_progress_bar;
+use rust_rocksdb::WriteBatch;
+use std::io::BufRead;
+use std::time::{Duration, Instant};
+
+const BATCH_SIZE: usize = 10_000;
+const SAMPLE_SIZE: usize = 10;
+const COMPACTION_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const COMPACTION_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    #[arg(long)]
+    keys_file: String,
+    /// Mark SST files for compaction when this many consecutive entries hold --deletion-count tombstones
+    #[arg(long, requires = "deletion_count")]
+    deletion_window: Option<usize>,
+    #[arg(long, requires = "deletion_window")]
+    deletion_count: Option<usize>,
+    /// Approximate number of keys in the keys file, to show a progress bar with ETA instead of a spinner
+    #[arg(long)]
+    progress_total: Option<u64>,
+    /// Only report what would be deleted, without writing anything
+    #[arg(long)]
+    dry_run: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let deletion_trigger = args.deletion_window.zip(args.deletion_count);
+    let db = if args.dry_run {
+        // bloom filters make the key_may_exist checks below cheap for missing keys
+        open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                bloom_filter_on_fast_open: true,
+                ..Default::default()
+            },
+        )?
+    } else {
+        open_rocksdb_for_write(
+            &args.db_dir,
+            WriteOpenOptions {
+                deletion_trigger,
+                ..Default::default()
+            },
+        )?
+    };
+
+    let reader = std::io::BufReader::new(std::fs::File::open(&args.keys_file)?);
+    let pb = make_progress_bar(args.progress_total);
+
+    let mut count_requested = 0;
+    let mut count_existing = 0;
+    let mut sample = vec![];
+    let mut write_batch = WriteBatch::default();
+    for line in reader.lines() {
+        let line = line?;
+        let key = line.trim();
+        if key.is_empty() {
+            continue;
+        }
+        count_requested += 1;
+
+        if db.key_may_exist(key) && db.get_pinned(key)?.is_some() {
+            count_existing += 1;
+            if sample.len() < SAMPLE_SIZE {
+                sample.push(key.to_string());
+            }
+        }
+        // the dry run still does all the reads above, it only skips the writes
+        if args.dry_run {
+            pb.inc(1);
+            continue;
+        }
+        write_batch.delete(key);
+
+        if write_batch.len() >= BATCH_SIZE {
+            db.write(&write_batch)?;
+            write_batch.clear();
+        }
+        pb.inc(1);
+    }
+    if !write_batch.is_empty() {
+        db.write(&write_batch)?;
+    }
+
+    pb.finish_with_message("done");
+    println!(
+        "Requested: {}\nExisting ({}): {}\nMissing: {}",
+        count_requested,
+        if args.dry_run {
+            "would be deleted"
+        } else {
+            "deleted"
+        },
+        count_existing,
+        count_requested - count_existing
+    );
+    if args.dry_run {
+        println!("Sample of keys that would be deleted:");
+        for key in &sample {
+            println!("{}", key);
+        }
+        println!("Dry run, nothing was deleted");
+        return Ok(());
+    }
+
+    if deletion_trigger.is_some() {
+        println!("Skipping manual compaction, tombstone-dense files get compacted automatically");
+        db.flush()?;
+        let live_sst_size = || -> Result<u64> {
+            Ok(db
+                .property_int_value("rocksdb.live-sst-files-size")?
+                .unwrap_or(0))
+        };
+        let size_before = live_sst_size()?;
+        let start = Instant::now();
+        loop {
+            let running = db
+                .property_int_value("rocksdb.num-running-compactions")?
+                .unwrap_or(0);
+            let pending = db
+                .property_int_value("rocksdb.compaction-pending")?
+                .unwrap_or(0);
+            if running == 0 && pending == 0 {
+                break;
+            }
+            if start.elapsed() > COMPACTION_WAIT_TIMEOUT {
+                println!(
+                    "Compactions still running after {:.2?}, they continue on the next open",
+                    start.elapsed()
+                );
+                break;
+            }
+            std::thread::sleep(COMPACTION_POLL_INTERVAL);
+        }
+        println!(
+            "Live SST size: {} bytes after the flush, {} bytes after background compactions ({:.2?})",
+            size_before,
+            live_sst_size()?,
+            start.elapsed()
+        );
+        return Ok(());
+    }
+
+    // Compaction
+    println!("========== Compacting ==========");
+    db.compact_range(None::<&[u8]>, None::<&[u8]>);
+
+    Ok(())
+}
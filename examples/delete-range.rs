@@ -0,0 +1,115 @@
+//! Delete a contiguous range of keys from RocksDB.
+//!
+//! Usage:
+//! ```
+//! cargo run --example delete-range -- --db-dir data.rocksdb --start-key 000 --end-key 100
+//! cargo run --example delete-range -- --db-dir data.rocksdb --start-key 000 --end-key 100 --dry-run
+//! ```
+//!
+//! This will count the keys in `[--start-key, --end-key)`, delete them all with `delete_range`, a single range
+//! tombstone in a WriteBatch, then scan the range again and print the remaining count, which must be zero.
+//! Keys sort bytewise, so a range of hex prefixes like the one above covers every key starting with 000 to 0ff.
+//! The tombstone hides the keys from reads at once, while their space is only reclaimed by later compactions.
+//! A start key that isn't before the end key is an error, reported before the DB is opened.
+//! With `--dry-run`, the DB is opened read-only and the range is only counted: it reports how many keys would be
+//! deleted, with the first SAMPLE_SIZE of them, and writes nothing.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::parscan::range_iterator;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, WriteOpenOptions, delete_range, open_rocksdb_for_read_only,
+    open_rocksdb_for_write,
+};
+use rust_rocksdb::DB;
+use std::time::Instant;
+
+const SAMPLE_SIZE: usize = 10;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// First key of the range, inclusive
+    #[arg(long)]
+    start_key: String,
+    /// End of the range, exclusive
+    #[arg(long)]
+    end_key: String,
+    /// Only report what would be deleted, without writing anything
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Count the keys in `[start, end)`, keeping the first `sample_size` of them.
+fn count_range(
+    db: &DB,
+    start: &[u8],
+    end: &[u8],
+    sample_size: usize,
+) -> Result<(usize, Vec<Box<[u8]>>)> {
+    let mut count = 0;
+    let mut sample = vec![];
+    for item in range_iterator(db, Some(start.to_vec()), Some(end.to_vec())) {
+        let (key, _value) = item?;
+        if sample.len() < sample_size {
+            sample.push(key);
+        }
+        count += 1;
+    }
+    Ok((count, sample))
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let (start, end) = (args.start_key.as_bytes(), args.end_key.as_bytes());
+    if start >= end {
+        anyhow::bail!(
+            "--start-key {:?} must sort before --end-key {:?}",
+            args.start_key,
+            args.end_key
+        );
+    }
+    if args.dry_run {
+        let db = open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )?;
+        let (count, sample) = count_range(&db, start, end, SAMPLE_SIZE)?;
+        println!(
+            "Keys in [{}, {}) that would be deleted: {}",
+            args.start_key, args.end_key, count
+        );
+        if !sample.is_empty() {
+            println!("Sample of keys that would be deleted:");
+            for key in &sample {
+                println!("{}", String::from_utf8_lossy(key));
+            }
+        }
+        println!("Dry run, nothing was deleted");
+        return Ok(());
+    }
+    let db = open_rocksdb_for_write(&args.db_dir, WriteOpenOptions::default())?;
+
+    let (before, _) = count_range(&db, start, end, 0)?;
+    let start_time = Instant::now();
+    delete_range(&db, start, end)?;
+    println!(
+        "Deleted [{}, {}) holding {} keys in {:.2?}",
+        args.start_key,
+        args.end_key,
+        before,
+        start_time.elapsed()
+    );
+
+    let (remaining, _) = count_range(&db, start, end, 0)?;
+    println!("Remaining keys in range: {}", remaining);
+    if remaining != 0 {
+        anyhow::bail!("{} keys left in the deleted range", remaining);
+    }
+    Ok(())
+}
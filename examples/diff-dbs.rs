@@ -0,0 +1,142 @@
+//! Diff the keys and values of two DBs.
+//!
+//! Usage:
+//! ```
+//! cargo run --example diff-dbs -- --db-dir-left data1.rocksdb --db-dir-right data2.rocksdb
+//! cargo run --example diff-dbs -- --db-dir-left data1.rocksdb --db-dir-right data2.rocksdb --normalize json-canonical
+//! ```
+//!
+//! This will walk both DBs in key order with two pointers, like two-pointer-serial, and compare the values
+//! of the keys present in both. It prints the first MAX_PRINTED differing keys and the totals.
+//! Values are compared byte for byte by default. With `--normalize`, both values are normalized first,
+//! so differences that don't change their meaning aren't reported:
+//! `trim` strips leading and trailing whitespace, `json-canonical` parses values as JSON and compares them
+//! re-serialized with sorted object keys and no whitespace. Values that aren't valid JSON are compared raw.
+
+use anyhow::Result;
+use clap::{Parser, ValueEnum};
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, open_rocksdb_for_read_only, timed_open,
+};
+use rocksdb_examples::utils::make_progress_bar;
+use rust_rocksdb::IteratorMode;
+use std::borrow::Cow;
+
+const MAX_PRINTED: usize = 10;
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum Normalize {
+    #[default]
+    Raw,
+    Trim,
+    JsonCanonical,
+}
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir_left: String,
+    #[arg(long)]
+    db_dir_right: String,
+    /// How to normalize values before comparing them
+    #[arg(long, value_enum, default_value_t)]
+    normalize: Normalize,
+}
+
+fn normalize(value: &[u8], normalize: Normalize) -> Cow<'_, [u8]> {
+    match normalize {
+        Normalize::Raw => Cow::Borrowed(value),
+        Normalize::Trim => Cow::Borrowed(value.trim_ascii()),
+        // serde_json's maps are sorted by key, so re-serializing canonicalizes field order
+        Normalize::JsonCanonical => match serde_json::from_slice::<serde_json::Value>(value) {
+            Ok(json) => Cow::Owned(json.to_string().into_bytes()),
+            Err(_) => Cow::Borrowed(value),
+        },
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db_left = timed_open(&args.db_dir_left, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir_left,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+    let db_right = timed_open(&args.db_dir_right, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir_right,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+
+    let pb = make_progress_bar(None);
+
+    let mut db_iter_left = db_left.full_iterator(IteratorMode::Start);
+    let mut db_iter_right = db_right.full_iterator(IteratorMode::Start);
+
+    let mut count_left_only = 0_usize;
+    let mut count_right_only = 0_usize;
+    let mut count_same = 0_usize;
+    let mut count_different = 0_usize;
+    let mut item_left = db_iter_left.next().transpose()?;
+    let mut item_right = db_iter_right.next().transpose()?;
+
+    while let (Some((key_left, value_left)), Some((key_right, value_right))) =
+        (item_left.as_ref(), item_right.as_ref())
+    {
+        if key_left == key_right {
+            if normalize(value_left, args.normalize) == normalize(value_right, args.normalize) {
+                count_same += 1;
+            } else {
+                count_different += 1;
+                if count_different <= MAX_PRINTED {
+                    pb.println(format!(
+                        "key: {} left: {} right: {}",
+                        String::from_utf8_lossy(key_left),
+                        String::from_utf8_lossy(value_left),
+                        String::from_utf8_lossy(value_right)
+                    ));
+                }
+            }
+            item_left = db_iter_left.next().transpose()?;
+            item_right = db_iter_right.next().transpose()?;
+        } else if key_left < key_right {
+            count_left_only += 1;
+            item_left = db_iter_left.next().transpose()?;
+        } else {
+            count_right_only += 1;
+            item_right = db_iter_right.next().transpose()?;
+        }
+        pb.inc(1);
+    }
+
+    while item_left.is_some() {
+        count_left_only += 1;
+        item_left = db_iter_left.next().transpose()?;
+        pb.inc(1);
+    }
+
+    while item_right.is_some() {
+        count_right_only += 1;
+        item_right = db_iter_right.next().transpose()?;
+        pb.inc(1);
+    }
+
+    pb.finish_with_message("done");
+    println!(
+        "Keys:\nleft only: {}\nright only: {}\nboth: {}",
+        count_left_only,
+        count_right_only,
+        count_same + count_different
+    );
+    println!("Values:\nsame: {count_same}\ndifferent: {count_different}");
+    Ok(())
+}
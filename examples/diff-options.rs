@@ -0,0 +1,90 @@
+//! Diff the options of two DBs.
+//!
+//! Usage:
+//! ```
+//! cargo run --example diff-options -- --db-dir-left data1.rocksdb --db-dir-right data2.rocksdb
+//! ```
+//!
+//! This will read each DB's latest OPTIONS file, which RocksDB writes on every open with the full configuration
+//! (DB options, per column family options and their table options), and print the settings that differ side by side.
+//! Only the files are read, the DBs aren't opened, so it also works on a DB that another process has open.
+//! Settings that change how keys are ordered, found or merged (CORRECTNESS_OPTIONS, e.g. the comparator or the
+//! prefix extractor) are listed first: a DB read with a different one returns wrong results, not just slower ones.
+//! The rest, like compression, block size or write buffer sizes, only affect performance and space.
+//! Exits with an error if any correctness setting differs.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{latest_options_file, parse_options_file};
+use std::collections::BTreeSet;
+
+/// Option names whose mismatch changes what reads return, not only how fast.
+const CORRECTNESS_OPTIONS: &[&str] = &[
+    "comparator",
+    "prefix_extractor",
+    "merge_operator",
+    "compaction_filter",
+    "compaction_filter_factory",
+    "persist_user_defined_timestamps",
+];
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir_left: String,
+    #[arg(long)]
+    db_dir_right: String,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let left_path = latest_options_file(&args.db_dir_left)?;
+    let right_path = latest_options_file(&args.db_dir_right)?;
+    println!("left: {}", left_path.display());
+    println!("right: {}", right_path.display());
+    let left = parse_options_file(&left_path)?;
+    let right = parse_options_file(&right_path)?;
+
+    let mut correctness = vec![];
+    let mut other = vec![];
+    let names: BTreeSet<&String> = left.keys().chain(right.keys()).collect();
+    for name in names {
+        let left_value = left.get(name).map_or("(unset)", String::as_str);
+        let right_value = right.get(name).map_or("(unset)", String::as_str);
+        if left_value == right_value {
+            continue;
+        }
+        // names are "section.option", and section headers can contain dots only inside quotes
+        let option = name.rsplit('.').next().unwrap_or(name);
+        if CORRECTNESS_OPTIONS.contains(&option) {
+            correctness.push((name, left_value, right_value));
+        } else {
+            other.push((name, left_value, right_value));
+        }
+    }
+
+    let width = correctness
+        .iter()
+        .chain(other.iter())
+        .map(|(name, _, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    for (title, diffs) in [
+        ("Correctness settings", &correctness),
+        ("Performance settings", &other),
+    ] {
+        println!("========== {}: {} differ ==========", title, diffs.len());
+        for (name, left_value, right_value) in diffs {
+            println!("{:<width$}  {}  |  {}", name, left_value, right_value);
+        }
+    }
+
+    if !correctness.is_empty() {
+        anyhow::bail!(
+            "{} correctness settings differ, reading one DB with the other's options may return wrong results",
+            correctness.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! Estimate value entropy of RocksDB by sampling.
+//!
+//! Usage:
+//! ```
+//! cargo run --example entropy-sample -- --db-dir data.rocksdb --sample-size 1000
+//! ```
+//!
+//! This will sample values by seeking to random hex prefixes and taking the next entry,
+//! then compute the byte-frequency (Shannon) entropy over all sampled value bytes.
+//! Entropy is reported in bits per byte (0 to 8) and as a ratio of 8, a rough bound on the compressed-to-raw size,
+//! followed by a compression recommendation: None for near-random data, Lz4 for moderately compressible data,
+//! Zstd for highly compressible data.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, open_rocksdb_for_read_only, timed_open,
+};
+use rocksdb_examples::utils::{generate_random_hex_string, make_progress_bar};
+use rust_rocksdb::{Direction, IteratorMode};
+
+const PREFIX_LEN: usize = 8;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Number of values to sample
+    #[arg(long, default_value_t = 1000)]
+    sample_size: usize,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db = timed_open(&args.db_dir, || {
+        open_rocksdb_for_read_only(&args.db_dir, ReadOnlyOpenOptions::default())
+    })?;
+
+    let pb = make_progress_bar(Some(args.sample_size as u64));
+    let mut histogram = [0_u64; 256];
+    let mut count = 0;
+    let mut total_bytes = 0_u64;
+    for _ in 0..args.sample_size {
+        let prefix = generate_random_hex_string(PREFIX_LEN);
+        let mut db_iter =
+            db.full_iterator(IteratorMode::From(prefix.as_bytes(), Direction::Forward));
+        if let Some(item) = db_iter.next() {
+            let (_key, value) = item?;
+            for &b in value.iter() {
+                histogram[b as usize] += 1;
+            }
+            total_bytes += value.len() as u64;
+            count += 1;
+        }
+        pb.inc(1);
+    }
+    pb.finish_with_message("done");
+
+    if total_bytes == 0 {
+        println!("No values sampled");
+        return Ok(());
+    }
+
+    let entropy: f64 = histogram
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / total_bytes as f64;
+            -p * p.log2()
+        })
+        .sum();
+    let ratio = entropy / 8.0;
+    let recommendation = if ratio > 0.9 {
+        "None (values look random, compression would mostly waste CPU)"
+    } else if ratio > 0.6 {
+        "Lz4 (moderately compressible, favor speed)"
+    } else {
+        "Zstd (highly compressible, favor size)"
+    };
+
+    println!("Sampled: {} values, {} bytes", count, total_bytes);
+    println!(
+        "Average value size: {:.1} bytes",
+        total_bytes as f64 / count as f64
+    );
+    println!("Entropy: {:.3} bits/byte (ratio {:.3})", entropy, ratio);
+    println!("Recommendation: {}", recommendation);
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! Export RocksDB to a binary dump file.
+//!
+//! Usage:
+//! ```
+//! cargo run --example export-binary -- --db-dir data.rocksdb --out data.dump
+//! cargo run --example import-binary -- --db-dir restored.rocksdb --input data.dump
+//! ```
+//!
+//! This will write every entry of the DB, in key order, to OUT as length-prefixed records
+//! `[varint key len][key][varint value len][value]` after a header of DUMP_MAGIC and a version byte.
+//! Keys and values are copied byte for byte, so unlike a hex or TSV dump it round-trips binary data exactly
+//! and adds only a few bytes per entry. The file is streamable; import-binary reads it back into a new DB.
+//! It's written to a temp file next to OUT and renamed into place once complete.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, open_rocksdb_for_read_only, timed_open,
+};
+use rocksdb_examples::utils::{make_progress_bar, write_dump_header, write_dump_record};
+use rust_rocksdb::IteratorMode;
+use std::io::BufWriter;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Path of the dump file
+    #[arg(long)]
+    out: String,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db = timed_open(&args.db_dir, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+
+    let tmp_path = format!("{}.tmp", args.out);
+    let mut writer = BufWriter::new(std::fs::File::create(&tmp_path)?);
+    write_dump_header(&mut writer)?;
+
+    let pb = make_progress_bar(None);
+    let mut count = 0;
+    for item in db.full_iterator(IteratorMode::Start) {
+        let (key, value) = item?;
+        write_dump_record(&mut writer, &key, &value)?;
+        count += 1;
+        pb.inc(1);
+    }
+    pb.finish_with_message("done");
+
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, &args.out)?;
+    println!(
+        "Exported {} entries ({} bytes) to {}",
+        count,
+        std::fs::metadata(&args.out)?.len(),
+        args.out
+    );
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! Load unsorted input into RocksDB with a memory-bounded external merge sort.
+//!
+//! Usage:
+//! ```
+//! cargo run --example external-sort -- --db-dir data.rocksdb --input unsorted.tsv
+//! cargo run --example external-sort -- --db-dir data.rocksdb --input - --memory-budget-mb 1024 < unsorted.tsv
+//! cargo run --example external-sort -- --db-dir data.rocksdb --input unsorted.csv --field-separator , --skip-header
+//! ```
+//!
+//! This will read `key<TAB>value` lines in any order (`--input -` reads stdin) and sort them in two passes.
+//! Lines are split at the first `--field-separator` (tab by default), so keys can't contain it but values can:
+//! `a<TAB>b<TAB>c` is key `a` with value `b<TAB>c`. Lines are taken as raw bytes, with a trailing `\r` dropped,
+//! and `--skip-header` ignores the first line.
+//! Entries are collected until they take about `--memory-budget-mb`, then sorted in parallel by rayon's default thread
+//! pool (RAYON_NUM_THREADS) and spilled to a run file in a scratch dir, as binary dump records.
+//! The scratch dir is created under `--temp-dir`, or TMPDIR by default, so put it on fast local storage when the DB
+//! lives on a slow mount; it's removed at the end, also when the run fails.
+//! Then all runs are merged at once with a heap holding one entry per run, which streams the sorted entries
+//! into a single SstFileWriter, and the file is ingested into the DB (copied if it's on another filesystem). SstFileWriter needs strictly increasing keys,
+//! so a key that appears more than once keeps its last value, like repeated puts would.
+//! Memory stays around the budget in the first pass and one entry plus a read buffer per run in the merge,
+//! so the input can be much larger than memory; a smaller budget means more runs, each an open file during the merge.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use rayon::prelude::*;
+use rocksdb_examples::rocksdb_utils::{BulkOpenOptions, open_rocksdb_for_bulk_ingestion};
+use rocksdb_examples::utils::{
+    ScratchDir, make_progress_bar, parse_separator, read_dump_record, split_first,
+    write_dump_record,
+};
+use rust_rocksdb::{IngestExternalFileOptions, Options, SstFileWriter};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const ROCKSDB_NUM_LEVELS: i32 = 7;
+/// Rough per-entry memory on top of the key and value bytes: the two Vecs and the run's slot.
+const ENTRY_OVERHEAD: usize = 64;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Path of the key<TAB>value input (see --field-separator), or - for stdin
+    #[arg(long)]
+    input: String,
+    /// Byte between key and value, as one ASCII char or 0x-prefixed byte; only its first occurrence on a line splits
+    #[arg(long, default_value = "0x09", value_parser = parse_separator)]
+    field_separator: u8,
+    /// Ignore the first line of the input, e.g. a column header
+    #[arg(long)]
+    skip_header: bool,
+    /// Memory for the entries of one sorted run, in MB
+    #[arg(long, default_value_t = 256)]
+    memory_budget_mb: usize,
+    /// Parent directory of the scratch dir for the sorted runs and the SST file (defaults to TMPDIR)
+    #[arg(long)]
+    temp_dir: Option<String>,
+}
+
+/// Sort `run` by key, keeping only the last value of each key, write it to run file `index` in `scratch_dir`,
+/// and clear it for the next run.
+fn spill_run(
+    run: &mut Vec<(Vec<u8>, Vec<u8>)>,
+    scratch_dir: &Path,
+    index: usize,
+) -> Result<PathBuf> {
+    // stable, so entries with the same key stay in input order and the last one is the latest
+    run.par_sort_by(|a, b| a.0.cmp(&b.0));
+    let path = scratch_dir.join(format!("run-{:06}.bin", index));
+    let mut writer = BufWriter::new(std::fs::File::create(&path)?);
+    for (i, (key, value)) in run.iter().enumerate() {
+        if run.get(i + 1).is_some_and(|next| next.0 == *key) {
+            continue;
+        }
+        write_dump_record(&mut writer, key, value)?;
+    }
+    writer.flush()?;
+    run.clear();
+    Ok(path)
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    if args.memory_budget_mb == 0 {
+        anyhow::bail!("--memory-budget-mb must be at least 1");
+    }
+    // removed on drop, so also when a pass errors out
+    let scratch_dir = ScratchDir::create(args.temp_dir.as_deref(), "external-sort")?;
+    let input: Box<dyn BufRead> = if args.input == "-" {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(
+            std::fs::File::open(&args.input).with_context(|| format!("opening {}", args.input))?,
+        ))
+    };
+
+    // first pass: sorted runs of at most the memory budget
+    let budget = args.memory_budget_mb * 1024 * 1024;
+    let pb = make_progress_bar(None);
+    let start = Instant::now();
+    let mut run_paths = vec![];
+    let mut run = vec![];
+    let mut run_bytes = 0;
+    let mut count = 0;
+    for (i, line) in input.split(b'\n').enumerate() {
+        let line = line.with_context(|| format!("reading line {} of {}", i + 1, args.input))?;
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        if line.is_empty() || (i == 0 && args.skip_header) {
+            continue;
+        }
+        let (key, value) = split_first(line, args.field_separator).with_context(|| {
+            format!(
+                "line {}: no {:?} between key and value",
+                i + 1,
+                args.field_separator as char
+            )
+        })?;
+        run_bytes += key.len() + value.len() + ENTRY_OVERHEAD;
+        run.push((key.to_vec(), value.to_vec()));
+        count += 1;
+        pb.inc(1);
+        if run_bytes >= budget {
+            run_paths.push(spill_run(&mut run, scratch_dir.path(), run_paths.len())?);
+            run_bytes = 0;
+        }
+    }
+    if !run.is_empty() {
+        run_paths.push(spill_run(&mut run, scratch_dir.path(), run_paths.len())?);
+    }
+    pb.finish_with_message("done");
+    println!(
+        "Sorted {} entries into {} runs in {:.2?}",
+        count,
+        run_paths.len(),
+        start.elapsed()
+    );
+    if count == 0 {
+        println!("No entries in {}, nothing loaded", args.input);
+        return Ok(());
+    }
+
+    // second pass: k-way merge of the runs into one SST file
+    let start = Instant::now();
+    let mut readers = run_paths
+        .iter()
+        .map(|path| -> Result<_> { Ok(BufReader::new(std::fs::File::open(path)?)) })
+        .collect::<Result<Vec<_>>>()?;
+    // ordered by key, then run index, so equal keys come out in input order; each run has one entry in the heap
+    let mut heap = BinaryHeap::new();
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some((key, value)) = read_dump_record(reader)? {
+            heap.push(Reverse((key, i, value)));
+        }
+    }
+
+    // ingested files land in the bottommost level, so use its compression
+    let mut opts = Options::default();
+    opts.set_compression_type(rust_rocksdb::DBCompressionType::Zstd);
+    let mut writer = SstFileWriter::create(&opts);
+    let sst_path = scratch_dir.path().join("sorted.sst");
+    writer.open(&sst_path)?;
+
+    let pb = make_progress_bar(Some(count as u64));
+    let mut written = 0;
+    let mut pending: Option<(Vec<u8>, Vec<u8>)> = None;
+    while let Some(Reverse((key, i, value))) = heap.pop() {
+        if let Some((next_key, next_value)) = read_dump_record(&mut readers[i])? {
+            heap.push(Reverse((next_key, i, next_value)));
+        }
+        // a later run's value for the same key replaces the pending one
+        if let Some((pending_key, pending_value)) = pending.take_if(|(k, _)| *k != key) {
+            writer.put(&pending_key, &pending_value)?;
+            written += 1;
+        }
+        pending = Some((key, value));
+        pb.inc(1);
+    }
+    if let Some((pending_key, pending_value)) = pending {
+        writer.put(&pending_key, &pending_value)?;
+        written += 1;
+    }
+    writer.finish()?;
+    pb.finish_with_message("done");
+    println!(
+        "Merged {} runs into {} unique entries in {:.2?}",
+        run_paths.len(),
+        written,
+        start.elapsed()
+    );
+
+    let db = open_rocksdb_for_bulk_ingestion(
+        &args.db_dir,
+        BulkOpenOptions {
+            num_levels: Some(ROCKSDB_NUM_LEVELS),
+            ..Default::default()
+        },
+    )?;
+    let mut ingest_opts = IngestExternalFileOptions::default();
+    ingest_opts.set_move_files(true);
+    db.ingest_external_file_opts(&ingest_opts, vec![sst_path])?;
+    println!("Ingested {} entries into {}", written, args.db_dir);
+    Ok(())
+}
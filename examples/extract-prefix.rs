@@ -0,0 +1,114 @@
+//! Extract the keys under a prefix into a new DB.
+//!
+//! Usage:
+//! ```
+//! cargo run --example extract-prefix -- --db-dir data.rocksdb --out shard-00a.rocksdb --prefix 00a
+//! cargo run --example extract-prefix -- --db-dir binary.rocksdb --out shard-ff.rocksdb --prefix ff --binary
+//! ```
+//!
+//! This will iterate exactly the keys starting with PREFIX, bounded to `[prefix, next_prefix(prefix))`,
+//! write them in order to a single SST file with SstFileWriter, and ingest it into a new bulk-loaded DB at OUT.
+//! The source is already sorted, so the SST ingest fast path applies. The count and size extracted are printed.
+//! PREFIX is matched as text against hex string keys, or with `--binary`, hex-decoded and matched against raw
+//! bytes. A prefix of only 0xff bytes has no successor, so its scan has no upper bound, and every key from the
+//! prefix to the end of the DB starts with it.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    BulkOpenOptions, ReadOnlyOpenOptions, next_prefix, open_rocksdb_for_bulk_ingestion,
+    open_rocksdb_for_read_only, timed_open,
+};
+use rocksdb_examples::utils::{make_progress_bar, validate_hex_key};
+use rust_rocksdb::{IngestExternalFileOptions, IteratorMode, Options, ReadOptions, SstFileWriter};
+
+const ROCKSDB_NUM_LEVELS: i32 = 7;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Directory of the new DB
+    #[arg(long)]
+    out: String,
+    /// Prefix of the keys to extract, as lowercase hex
+    #[arg(long)]
+    prefix: String,
+    /// Hex-decode the prefix and match it against raw key bytes
+    #[arg(long)]
+    binary: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    validate_hex_key(args.prefix.as_bytes(), None)
+        .with_context(|| format!("invalid --prefix {:?}", args.prefix))?;
+    let prefix = if args.binary {
+        hex::decode(&args.prefix)?
+    } else {
+        args.prefix.as_bytes().to_vec()
+    };
+    let db = timed_open(&args.db_dir, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+
+    let mut readopts = ReadOptions::default();
+    readopts.set_total_order_seek(true);
+    readopts.set_iterate_lower_bound(prefix.clone());
+    if let Some(upper) = next_prefix(&prefix) {
+        readopts.set_iterate_upper_bound(upper);
+    }
+
+    // ingested files land in the bottommost level, so use its compression
+    let mut opts = Options::default();
+    opts.set_compression_type(rust_rocksdb::DBCompressionType::Zstd);
+    let mut writer = SstFileWriter::create(&opts);
+    let sst_path = format!("{}.extract.sst", args.out);
+    writer.open(&sst_path)?;
+
+    let pb = make_progress_bar(None);
+    let mut count = 0;
+    let mut bytes = 0;
+    for item in db.iterator_opt(IteratorMode::Start, readopts) {
+        let (key, value) = item?;
+        writer.put(&key, &value)?;
+        count += 1;
+        bytes += key.len() + value.len();
+        pb.inc(1);
+    }
+    pb.finish_with_message("done");
+
+    if count == 0 {
+        // SstFileWriter can't finish an empty file
+        drop(writer);
+        std::fs::remove_file(&sst_path)?;
+        println!("No keys start with {}, nothing extracted", args.prefix);
+        return Ok(());
+    }
+    writer.finish()?;
+    let sst_size = writer.file_size();
+
+    let out_db = open_rocksdb_for_bulk_ingestion(
+        &args.out,
+        BulkOpenOptions {
+            num_levels: Some(ROCKSDB_NUM_LEVELS),
+            ..Default::default()
+        },
+    )?;
+    let mut ingest_opts = IngestExternalFileOptions::default();
+    ingest_opts.set_move_files(true);
+    out_db.ingest_external_file_opts(&ingest_opts, vec![sst_path])?;
+
+    println!(
+        "Extracted {} entries ({} bytes of keys and values, {} bytes on disk) with prefix {} into {}",
+        count, bytes, sst_size, args.prefix, args.out
+    );
+    Ok(())
+}
@@ -0,0 +1,44 @@
+//! Fingerprint RocksDB contents.
+//!
+//! Usage:
+//! ```
+//! cargo run --example fingerprint -- --db-dir data.rocksdb
+//! cargo run --example fingerprint -- --db-dir data.rocksdb --prefix 00a
+//! ```
+//!
+//! This will fold all keys and values (or only those starting with the prefix) in key order into one 64-bit hash.
+//! Identical DBs give identical fingerprints, which makes it a quick check that a copy or migration is exact:
+//! stronger than comparing counts, and much cheaper to compare than a full diff.
+//! Parallelized by rayon's default thread pool (RAYON_NUM_THREADS); the result doesn't depend on the thread count.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, db_fingerprint, open_rocksdb_for_read_only, timed_open,
+};
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    #[arg(long)]
+    prefix: Option<String>,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db = timed_open(&args.db_dir, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+
+    let fingerprint = db_fingerprint(&db, args.prefix.as_deref().map(str::as_bytes))?;
+    println!("Fingerprint: {:016x}", fingerprint);
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Benchmark open time and first-read latency of the read-only open modes.
+//!
+//! Usage:
+//! ```
+//! cargo run --release --example first-read-bench -- --db-dir data.rocksdb
+//! ```
+//!
+//! For each mode of `open_rocksdb_for_read_only` (fast open, fast open with bloom filters, full open),
+//! this will measure the open time, then the latency of the first NUM_GETS random gets
+//! (cold: index and filter blocks not loaded yet) and of the next NUM_GETS (warm), and print a table.
+//! Keys are random hex strings of KEY_LEN, so most gets are misses that exercise the index and filter path.
+//! The OS page cache is shared between the runs, so drop it between runs for fully cold numbers.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{ReadOnlyOpenOptions, open_rocksdb_for_read_only};
+use rocksdb_examples::utils::generate_random_hex_string;
+use rust_rocksdb::DB;
+use std::time::{Duration, Instant};
+
+const NUM_GETS: usize = 100;
+const KEY_LEN: usize = 16;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+}
+
+/// Average latency of NUM_GETS random gets.
+fn time_gets(db: &DB) -> Result<Duration> {
+    let keys: Vec<String> = (0..NUM_GETS)
+        .map(|_| generate_random_hex_string(KEY_LEN))
+        .collect();
+    let start = Instant::now();
+    for key in &keys {
+        db.get_pinned(key.as_bytes())?;
+    }
+    Ok(start.elapsed() / NUM_GETS as u32)
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+
+    println!(
+        "{:<24} {:>12} {:>12} {:>12}",
+        "mode", "open", "cold get", "warm get"
+    );
+    for (fast_open_for_iteration, bloom_filter_on_fast_open) in
+        [(true, false), (true, true), (false, false)]
+    {
+        let start = Instant::now();
+        let db = open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration,
+                bloom_filter_on_fast_open,
+                ..Default::default()
+            },
+        )?;
+        let open = start.elapsed();
+        let cold = time_gets(&db)?;
+        let warm = time_gets(&db)?;
+        drop(db);
+
+        println!(
+            "{:<24} {:>12.2?} {:>12.2?} {:>12.2?}",
+            if !fast_open_for_iteration {
+                "full".to_string()
+            } else if bloom_filter_on_fast_open {
+                "fast_open+bloom".to_string()
+            } else {
+                "fast_open".to_string()
+            },
+            open,
+            cold,
+            warm
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Count the original keys mapped to one value, using a map-reduce map output as a secondary index.
+//!
+//! Usage:
+//! ```
+//! cargo run --example map-reduce -- map --db-dir data.rocksdb --output-db-dir data-mapped.rocksdb
+//! cargo run --example index-count -- --db-dir data-mapped.rocksdb --value 0a3
+//! ```
+//!
+//! The map step writes one entry per original entry, keyed by value + sep + hex(key), with the original key as the
+//! value, so all original keys with the same value are adjacent. This will scan only those, with an iterator bounded
+//! to `[value + sep, value + (sep + 1))`, and print their count and up to SAMPLE_SIZE of the original keys.
+//! `--sep` must match the map step's (default 0x00). The default sorts before any byte of a value, so a value that's
+//! a prefix of another (e.g. "0a" and "0a3") never picks up the other's entries; a separator that sorts within the
+//! values' bytes doesn't guarantee that.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, next_prefix, open_rocksdb_for_read_only, timed_open,
+};
+use rocksdb_examples::utils::parse_separator;
+use rust_rocksdb::{IteratorMode, ReadOptions};
+
+const SAMPLE_SIZE: usize = 10;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    /// DB written by the map step of map-reduce
+    #[arg(long)]
+    db_dir: String,
+    /// Value whose original keys to count
+    #[arg(long)]
+    value: String,
+    /// Separator the map step was run with, as one ASCII char or 0x-prefixed byte
+    #[arg(long, default_value = "0x00", value_parser = parse_separator)]
+    sep: u8,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db = timed_open(&args.db_dir, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+
+    let lower = [args.value.as_bytes(), &[args.sep]].concat();
+    // value + (sep + 1), or for a 0xff separator the next value
+    let upper = next_prefix(&lower);
+    let mut readopts = ReadOptions::default();
+    readopts.set_total_order_seek(true);
+    readopts.set_iterate_lower_bound(lower);
+    if let Some(upper) = upper {
+        readopts.set_iterate_upper_bound(upper);
+    }
+
+    let mut count = 0;
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    for item in db.iterator_opt(IteratorMode::Start, readopts) {
+        let (_key, original_key) = item?;
+        if sample.len() < SAMPLE_SIZE {
+            sample.push(String::from_utf8_lossy(&original_key).into_owned());
+        }
+        count += 1;
+    }
+
+    println!("Original keys with value {}: {}", args.value, count);
+    for original_key in &sample {
+        println!("  {}", original_key);
+    }
+    if count > sample.len() {
+        println!("  ... and {} more", count - sample.len());
+    }
+    Ok(())
+}
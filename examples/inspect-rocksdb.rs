@@ -4,22 +4,83 @@
 //! ```
 //! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --one-by-one
 //! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --print-stats
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --bounds
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --validate --expected-len 16
 //! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --count
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --count --balanced --num-ranges 16
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --count --balanced --stored-split-points
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --count --state-file count.state
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --count --reverse
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --count --allow-concurrent
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --count --open-threads 4
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --count --pattern '^00[0-7].*ff$'
 //! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --key 00000a2865d3d6f2792de5adf5cc9193
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --interactive-get < keys.txt
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --one-by-one --decode json
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --sample 20
 //! ```
 //!
 //! This will inspect the DB.
 //! Key and value are random raw bytes encoded as hex strings.
 //! You can inspect the DB by key, one by one, printing stats, or counting the number of keys that start with a given prefix.
+//! `--bounds` prints the smallest and largest keys with one seek to each end, without a scan.
+//! `--validate` scans all keys in parallel and counts those that aren't lowercase hex of the expected length
+//! (`--expected-len`, or the detected one), per violation type, to catch producers breaking the hex convention.
+//! The key format (hex, binary or unknown) is detected from the first keys on open and printed; binary keys
+//! are shown hex-encoded, and `--count` warns when the keys aren't hex, since its shards are hex prefixes.
+//! With `--balanced`, the count is split into ranges of roughly equal on-disk size (from `get_approximate_sizes`)
+//! instead of fixed hex prefixes, which keeps threads evenly loaded on skewed data. Per-range counts are printed.
+//! With `--stored-split-points`, the ranges come from split points stored next to the DB by the split-points example
+//! instead, so repeated counts skip the approximate sizes and split the same way every time.
+//! With `--state-file`, each prefix shard's count is appended to the file once the shard completes,
+//! so an interrupted count can be rerun with the same file and only the remaining shards are scanned.
+//! With `--reverse`, each prefix shard is scanned in descending key order, from its last key; the count is the same.
+//! Read-only opens don't modify the DB or take its LOCK, but only see the files present when they opened;
+//! with `--allow-concurrent`, the DB is opened as a secondary instance instead, which stays valid while a writer keeps
+//! compacting. Its info logs go to a per-process temp dir, removed on exit, also after an error.
+//! The open time and SST file count are printed; `--open-threads` sets the number of threads opening SST files
+//! (defaults to num_cpus for a read-only open, RocksDB's default for a secondary), which mostly matters for DBs with many files. `--block-cache-mb` caps the block cache,
+//! e.g. when several inspect processes share a box; `--print-stats` shows it as block-cache-capacity.
+//! With `--pattern`, only keys whose UTF-8 form matches the regex are counted. That's a full scan, since an arbitrary
+//! pattern can't prune key ranges, unless the pattern starts with `^` and a literal (e.g. `^00a`), in which case
+//! only the shards under that literal prefix are scanned, starting from it.
+//! `--interactive-get` reads keys from stdin, one per line, and prints each value or MISSING, until EOF or `q`.
+//! With `--hex-key`, each line is hex-decoded first, for binary keys.
+//! `--decode` prints the values of `--key`, `--interactive-get` and `--one-by-one` decoded instead of as text:
+//! `json` pretty-prints them as generic JSON, for structured values, and `hex` hex-encodes them, for binary ones.
+//! A value that doesn't parse as JSON is shown hex-encoded, so one malformed value doesn't stop the inspection.
+//! `escaped` prints printable ASCII as is and escapes every other byte (e.g. `\x00`, `\n`), which keeps text readable
+//! while showing exactly which bytes are binary. Without `--decode`, a value that isn't valid UTF-8 is shown escaped
+//! too, instead of with the lossy replacement characters that would hide its bytes. `--key` also prints the value's
+//! length in bytes.
+//! `--sample N` prints N entries sampled uniformly from the whole DB with reservoir sampling (Vitter's algorithm R),
+//! a representative peek that isn't biased toward the start like `--one-by-one`. Reservoir sampling is a single pass
+//! that never needs the total count up front; it reads the whole DB only so that every entry can be chosen.
+//! Random hex keys are already hash-like, so a cheaper peek that skips most of the DB is to scan a few random hex
+//! prefixes, at the cost of not sampling uniformly across them.
 
 use anyhow::Result;
 use clap::Parser;
+use rand::RngExt;
 use rayon::prelude::*;
-use rocksdb_examples::rocksdb_utils::{open_rocksdb_for_read_only, print_rocksdb_stats};
-use rocksdb_examples::utils::{generate_consecutive_hex_strings, handle_input, make_progress_bar};
+use regex::Regex;
+use rocksdb_examples::parscan::{par_scan, range_iterator, range_iterator_reverse};
+use rocksdb_examples::rocksdb_utils::{
+    DbFormat, ReadOnlyOpenOptions, detect_db_format, key_ranges_from_split_points,
+    load_split_points, next_prefix, open_rocksdb_as_secondary, open_rocksdb_for_read_only,
+    print_rocksdb_stats, split_key_ranges_by_size, timed_open,
+};
+use rocksdb_examples::utils::{
+    Decode, HexKeyError, ScratchDir, display_value, generate_consecutive_hex_strings, handle_input,
+    make_progress_bar, validate_hex_key,
+};
 use rust_rocksdb::{Direction, IteratorMode};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::sync::Mutex;
 
 #[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
 struct Cli {
     #[clap(long)]
     db_dir: String,
@@ -29,62 +90,411 @@ struct Cli {
     one_by_one: bool,
     #[clap(long)]
     print_stats: bool,
+    /// Print the smallest and largest keys
+    #[clap(long)]
+    bounds: bool,
+    /// Count keys that aren't lowercase hex of the expected length
+    #[clap(long)]
+    validate: bool,
+    /// Key length for --validate (defaults to the detected one)
+    #[clap(long)]
+    expected_len: Option<usize>,
     #[clap(long)]
     count: bool,
+    /// With --count, split the work into ranges of roughly equal on-disk size
+    #[clap(long)]
+    balanced: bool,
+    /// Number of ranges for --balanced (defaults to the number of rayon threads)
+    #[clap(long)]
+    num_ranges: Option<usize>,
+    /// With --balanced, use the split points stored by the split-points example instead of computing them
+    #[clap(long)]
+    stored_split_points: bool,
+    /// With --count, scan each prefix shard in descending key order
+    #[clap(long)]
+    reverse: bool,
+    /// With --count, record completed shards here and skip them when resuming an interrupted scan
+    #[clap(long)]
+    state_file: Option<String>,
+    /// Open as a secondary instance, safe alongside a live writer and other inspect processes
+    #[clap(long)]
+    allow_concurrent: bool,
+    /// Number of threads opening SST files (defaults to num_cpus, or RocksDB's default with --allow-concurrent)
+    #[clap(long)]
+    open_threads: Option<i32>,
+    /// Block cache size in MB, shown as block-cache-capacity by --print-stats (defaults to RocksDB's 32MB)
+    #[clap(long)]
+    block_cache_mb: Option<usize>,
+    /// With --count, only count keys matching this regex
+    #[clap(long)]
+    pattern: Option<String>,
+    /// Get the keys read from stdin, one per line, until EOF or "q"
+    #[clap(long)]
+    interactive_get: bool,
+    /// With --interactive-get, hex-decode each line into the raw key
+    #[clap(long)]
+    hex_key: bool,
+    /// Print this many entries sampled uniformly from the whole DB (reads all of it)
+    #[clap(long)]
+    sample: Option<usize>,
+    /// Print values decoded instead of as text
+    #[clap(long, value_enum)]
+    decode: Option<Decode>,
+}
+
+/// The literal text every match of `pattern` must start with, if it's anchored with `^`.
+/// Conservative: gives up on any alternation and drops a literal char followed by `*`, `?` or `{`.
+fn literal_prefix(pattern: &str) -> Option<String> {
+    let rest = pattern.strip_prefix('^')?;
+    if pattern.contains('|') {
+        return None;
+    }
+    let mut prefix = String::new();
+    for c in rest.chars() {
+        match c {
+            '*' | '?' | '{' => {
+                prefix.pop();
+                break;
+            }
+            // '+' still requires one occurrence of the last char, so it's kept
+            '\\' | '.' | '+' | '(' | ')' | '[' | ']' | '}' | '^' | '$' => break,
+            _ => prefix.push(c),
+        }
+    }
+    (!prefix.is_empty()).then_some(prefix)
+}
+
+/// Key as text, or hex-encoded for binary keys that wouldn't print.
+fn display_key(key: &[u8], format: DbFormat) -> String {
+    match format {
+        DbFormat::Binary => hex::encode(key),
+        _ => String::from_utf8_lossy(key).into_owned(),
+    }
 }
 
 fn main() -> Result<()> {
     let args = Cli::parse();
-    let db = open_rocksdb_for_read_only(&args.db_dir, true)?;
+    let pattern = args.pattern.as_deref().map(Regex::new).transpose()?;
+    let matches = |key: &[u8]| {
+        pattern
+            .as_ref()
+            .is_none_or(|re| re.is_match(&String::from_utf8_lossy(key)))
+    };
+    if pattern.is_some() && args.state_file.is_some() {
+        anyhow::bail!(
+            "--pattern can't be combined with --state-file, whose counts don't record the pattern"
+        );
+    }
+    // declared before the DB so that an early return closes the DB first, then removes the dir
+    let scratch_dir = match args.allow_concurrent {
+        true => Some(ScratchDir::create(None, "inspect-rocksdb-secondary")?),
+        false => None,
+    };
+    let block_cache_bytes = args.block_cache_mb.map(|mb| mb * 1024 * 1024);
+    let db = timed_open(&args.db_dir, || match &scratch_dir {
+        Some(scratch_dir) => open_rocksdb_as_secondary(
+            &args.db_dir,
+            &scratch_dir.path().to_string_lossy(),
+            args.open_threads,
+            block_cache_bytes,
+        ),
+        None => open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                file_opening_threads: args.open_threads,
+                block_cache_bytes,
+                ..Default::default()
+            },
+        ),
+    })?;
+    let format = detect_db_format(&db)?;
+    println!("Format: {}", format);
+    // the balanced ranges are unbounded at both ends, so they count every key whatever its format
+    if args.count && !args.balanced && !matches!(format, DbFormat::Hex { .. }) {
+        eprintln!(
+            "Warning: --count shards by hex prefix, keys outside 0-9a-f prefixes won't be counted"
+        );
+    }
 
     if let Some(key) = args.key {
         let key = key.as_bytes();
         let value = db.get(key)?.ok_or(anyhow::anyhow!("key not found"))?;
         println!(
-            "key: {} value: {}",
+            "key: {} value: {} ({} bytes)",
             String::from_utf8_lossy(key),
-            String::from_utf8_lossy(&value)
+            display_value(&value, args.decode),
+            value.len()
         );
+    } else if args.interactive_get {
+        for line in std::io::stdin().lock().lines() {
+            let line = line?;
+            let line = line.trim();
+            if line == "q" {
+                break;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let key = if args.hex_key {
+                match hex::decode(line) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        eprintln!("invalid hex key {:?}: {}", line, e);
+                        continue;
+                    }
+                }
+            } else {
+                line.as_bytes().to_vec()
+            };
+            match db.get_pinned(&key)? {
+                Some(value) => println!("{}", display_value(&value, args.decode)),
+                None => println!("MISSING"),
+            }
+        }
     } else if args.one_by_one {
         // iterator from start
-        let mut db_iter = db.full_iterator(IteratorMode::Start);
-        while let Some(Ok((key, value))) = db_iter.next() {
+        for item in db.full_iterator(IteratorMode::Start) {
+            let (key, value) = item?;
             println!(
                 "key: {} value: {}",
-                String::from_utf8_lossy(&key),
-                String::from_utf8_lossy(&value)
+                display_key(&key, format),
+                display_value(&value, args.decode)
             );
             handle_input();
         }
+    } else if let Some(n) = args.sample {
+        let mut rng = rand::rng();
+        let pb = make_progress_bar(None);
+        let mut reservoir = Vec::with_capacity(n);
+        for (i, item) in db.full_iterator(IteratorMode::Start).enumerate() {
+            let entry = item?;
+            pb.inc(1);
+            // algorithm R: entry i replaces a random slot with probability n / (i + 1)
+            if i < n {
+                reservoir.push(entry);
+            } else {
+                let j = rng.random_range(0..=i);
+                if j < n {
+                    reservoir[j] = entry;
+                }
+            }
+        }
+        pb.finish_with_message("done");
+        reservoir.sort();
+        for (key, value) in &reservoir {
+            println!(
+                "key: {} value: {}",
+                display_key(key, format),
+                display_value(value, args.decode)
+            );
+        }
+        println!("Sampled {} of {} entries", reservoir.len(), pb.position());
     } else if args.print_stats {
         print_rocksdb_stats(&db)?;
-    } else if args.count {
+    } else if args.validate {
+        let expected_len = args.expected_len.or(match format {
+            DbFormat::Hex { key_len } => Some(key_len),
+            _ => None,
+        });
+        let pb = make_progress_bar(None);
+        // (keys checked, violations by kind), folded per rayon job and then merged
+        let (checked, violations) = par_scan(&db, rayon::current_num_threads())
+            .try_fold(
+                || (0_usize, HashMap::new()),
+                |(checked, mut violations),
+                 item|
+                 -> Result<(usize, HashMap<&'static str, usize>)> {
+                    let (key, _value) = item?;
+                    pb.inc(1);
+                    if let Err(e) = validate_hex_key(&key, expected_len) {
+                        let kind = match e {
+                            HexKeyError::Empty => "empty",
+                            HexKeyError::NonHex(_) => "non-hex bytes",
+                            HexKeyError::Uppercase => "uppercase",
+                            HexKeyError::WrongLength { .. } => "wrong length",
+                        };
+                        *violations.entry(kind).or_insert(0) += 1;
+                    }
+                    Ok((checked + 1, violations))
+                },
+            )
+            .try_reduce(
+                || (0, HashMap::new()),
+                |(checked, mut acc), (other_checked, other)| {
+                    for (kind, count) in other {
+                        *acc.entry(kind).or_insert(0) += count;
+                    }
+                    Ok((checked + other_checked, acc))
+                },
+            )?;
+        pb.finish_with_message("done");
+
+        match expected_len {
+            Some(expected_len) => println!("Expected: lowercase hex of length {}", expected_len),
+            None => println!("Expected: lowercase hex of any length"),
+        }
+        println!("Keys checked: {}", checked);
+        if violations.is_empty() {
+            println!("No violations");
+        }
+        for (kind, count) in &violations {
+            println!("{}: {}", kind, count);
+        }
+    } else if args.bounds {
+        let first = db.iterator(IteratorMode::Start).next().transpose()?;
+        let last = db.iterator(IteratorMode::End).next().transpose()?;
+        match (first, last) {
+            (Some((min_key, _)), Some((max_key, _))) => {
+                println!("min: {}", display_key(&min_key, format));
+                println!("max: {}", display_key(&max_key, format));
+            }
+            _ => println!("(empty)"),
+        }
+    } else if args.count && args.balanced {
         let prefixes = generate_consecutive_hex_strings(3);
+        let num_threads = rayon::current_num_threads();
+        let ranges = if args.stored_split_points {
+            let split_points = load_split_points(&args.db_dir)?.ok_or(anyhow::anyhow!(
+                "no split points stored, run the split-points example with --store first"
+            ))?;
+            key_ranges_from_split_points(split_points)
+        } else {
+            let num_ranges = args.num_ranges.unwrap_or(num_threads);
+            if num_ranges == 0 || num_ranges > prefixes.len() {
+                anyhow::bail!("--num-ranges must be between 1 and {}", prefixes.len());
+            }
+            split_key_ranges_by_size(&db, &prefixes, num_ranges)
+        };
+        if ranges.len() < num_threads {
+            eprintln!(
+                "Warning: {} ranges for {num_threads} threads, some threads will be idle",
+                ranges.len()
+            );
+        }
+
+        let pb = make_progress_bar(Some(ranges.len() as u64));
+
+        let counts: Vec<usize> = ranges
+            .par_iter()
+            .map(|(lower, upper)| -> Result<usize> {
+                let mut count = 0;
+                for item in range_iterator(&db, lower.clone(), upper.clone()) {
+                    let (key, _value) = item?;
+                    if matches(&key) {
+                        count += 1;
+                    }
+                }
+                pb.inc(1);
+                Ok(count)
+            })
+            .collect::<Result<_>>()?;
+
+        pb.finish_with_message("done");
+        for ((lower, upper), count) in ranges.iter().zip(counts.iter()) {
+            let lower = lower
+                .as_deref()
+                .map(String::from_utf8_lossy)
+                .unwrap_or_default();
+            let upper = upper
+                .as_deref()
+                .map(String::from_utf8_lossy)
+                .unwrap_or_default();
+            println!("[{lower}, {upper}): {count}");
+        }
+        println!("Count: {}", counts.iter().sum::<usize>());
+    } else if args.count {
+        let literal = pattern
+            .as_ref()
+            .and_then(|re| literal_prefix(re.as_str()))
+            .unwrap_or_default();
+        // with a literal prefix, only the shards it overlaps can hold matches
+        let prefixes: Vec<String> = generate_consecutive_hex_strings(3)
+            .into_iter()
+            .filter(|prefix| prefix.starts_with(&literal) || literal.starts_with(prefix.as_str()))
+            .collect();
+
+        // shards completed by a previous run, as "prefix count" lines
+        let mut completed = HashMap::new();
+        let state_file = match &args.state_file {
+            Some(state_file) => {
+                if let Ok(contents) = std::fs::read_to_string(state_file) {
+                    for line in contents.lines() {
+                        // a torn last line from a crash fails to parse and that shard is simply redone
+                        if let Some((prefix, count)) = line.split_once(' ')
+                            && let Ok(count) = count.parse::<usize>()
+                        {
+                            completed.insert(prefix.to_string(), count);
+                        }
+                    }
+                }
+                let file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(state_file)?;
+                Some(Mutex::new(file))
+            }
+            None => None,
+        };
+        let count_resumed: usize = completed.values().sum();
+        if !completed.is_empty() {
+            println!(
+                "Resuming: {} shards already done, count so far: {}",
+                completed.len(),
+                count_resumed
+            );
+        }
+
+        let prefixes: Vec<String> = prefixes
+            .into_iter()
+            .filter(|prefix| !completed.contains_key(prefix))
+            .collect();
         let pb = make_progress_bar(Some(prefixes.len() as u64));
 
         let count = prefixes
             .into_par_iter()
-            .map(|prefix| {
-                let prefix = prefix.as_bytes();
-                let mut db_iter = db.full_iterator(IteratorMode::From(prefix, Direction::Forward));
+            .map(|prefix_str| -> Result<usize> {
+                // seek to the longer of the shard prefix and the literal prefix, one is a prefix of the other
+                let prefix = if literal.len() > prefix_str.len() {
+                    literal.as_bytes()
+                } else {
+                    prefix_str.as_bytes()
+                };
+                let db_iter = if args.reverse {
+                    range_iterator_reverse(&db, Some(prefix.to_vec()), next_prefix(prefix))
+                } else {
+                    db.full_iterator(IteratorMode::From(prefix, Direction::Forward))
+                };
                 let mut count = 0;
-                while let Some(Ok((key, _value))) = db_iter.next() {
-                    if &key[..prefix.len()] != prefix {
+                for item in db_iter {
+                    let (key, _value) = item?;
+                    if !key.starts_with(prefix) {
                         break;
                     }
-                    count += 1;
+                    if matches(&key) {
+                        count += 1;
+                    }
+                }
+                // only persisted once the shard is fully counted, so resumed totals stay exact
+                if let Some(state_file) = &state_file {
+                    let mut file = state_file.lock().unwrap();
+                    writeln!(file, "{} {}", prefix_str, count)?;
+                    file.flush()?;
                 }
                 pb.inc(1);
-                count
+                Ok(count)
             })
-            .reduce(|| 0_usize, |acc, c| acc + c);
+            .try_reduce(|| 0_usize, |acc, c| Ok(acc + c))?;
 
         pb.finish_with_message("done");
-        println!("Count: {}", count);
+        println!("Count: {}", count_resumed + count);
     } else {
-        println!("Invalid command");
-        std::process::exit(1);
+        // not process::exit, which would skip removing the secondary dir
+        anyhow::bail!("Invalid command");
     }
 
+    // close the DB before its secondary dir is removed
+    drop(db);
+    drop(scratch_dir);
     Ok(())
 }
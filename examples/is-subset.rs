@@ -0,0 +1,106 @@
+//! Check that one DB is a subset of another.
+//!
+//! Usage:
+//! ```
+//! cargo run --example is-subset -- --sub shard-00a.rocksdb --super data.rocksdb
+//! cargo run --example is-subset -- --sub shard-00a.rocksdb --super data.rocksdb --compare-values
+//! ```
+//!
+//! This will walk both DBs in key order, like two-pointer-serial, and check that every key of SUB is also in SUPER.
+//! With `--compare-values`, the values must be equal too. Only the SUPER side skips ahead; each SUB key is checked
+//! once, and the walk stops at the first missing or mismatched key, which is printed before exiting with status 1.
+//! Meant as a CI assertion after splitting or migrating a DB.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, open_rocksdb_for_read_only, timed_open,
+};
+use rocksdb_examples::utils::make_progress_bar;
+use rust_rocksdb::IteratorMode;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    /// DB whose keys must all be in --super
+    #[arg(long)]
+    sub: String,
+    #[arg(long = "super")]
+    super_: String,
+    /// Also require the values of the keys to be equal
+    #[arg(long)]
+    compare_values: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db_sub = timed_open(&args.sub, || {
+        open_rocksdb_for_read_only(
+            &args.sub,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+    let db_super = timed_open(&args.super_, || {
+        open_rocksdb_for_read_only(
+            &args.super_,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+
+    let pb = make_progress_bar(None);
+    let mut db_iter_super = db_super.full_iterator(IteratorMode::Start);
+    let mut item_super = db_iter_super.next().transpose()?;
+    let mut count = 0_usize;
+    for item_sub in db_sub.full_iterator(IteratorMode::Start) {
+        let (key_sub, value_sub) = item_sub?;
+        // skip the keys only SUPER has
+        while let Some((key_super, _)) = &item_super
+            && key_super < &key_sub
+        {
+            item_super = db_iter_super.next().transpose()?;
+        }
+        let problem = match &item_super {
+            Some((key_super, value_super)) if *key_super == key_sub => {
+                (args.compare_values && *value_super != value_sub).then(|| {
+                    format!(
+                        "value mismatch for key {}: sub {} super {}",
+                        String::from_utf8_lossy(&key_sub),
+                        String::from_utf8_lossy(&value_sub),
+                        String::from_utf8_lossy(value_super)
+                    )
+                })
+            }
+            _ => Some(format!(
+                "key {} is missing from super",
+                String::from_utf8_lossy(&key_sub)
+            )),
+        };
+        if let Some(problem) = problem {
+            pb.abandon();
+            println!("Not a subset after {} keys: {}", count, problem);
+            std::process::exit(1);
+        }
+        count += 1;
+        pb.inc(1);
+    }
+    pb.finish_with_message("done");
+
+    println!(
+        "{} is a subset of {}: all {} keys found{}",
+        args.sub,
+        args.super_,
+        count,
+        if args.compare_values {
+            " with equal values"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
@@ -2,158 +2,223 @@
 //!
 //! Usage:
 //! ```
-//! cargo run --example map-reduce -- --step map --db-dir data.rocksdb --output-db-dir data-mapped.rocksdb
-//! cargo run --example map-reduce -- --step reduce --db-dir data-mapped.rocksdb --output-db-dir data-reduced.rocksdb
+//! cargo run --example map-reduce -- map --db-dir data.rocksdb --output-db-dir data-mapped.rocksdb
+//! cargo run --example map-reduce -- reduce --db-dir data-mapped.rocksdb --output-db-dir data-reduced.rocksdb
+//! cargo run --example map-reduce -- map --keep-value --db-dir data.rocksdb --output-db-dir data-mapped.rocksdb
+//! cargo run --example map-reduce -- reduce --keep-value --db-dir data-mapped.rocksdb --output-db-dir data-reduced.rocksdb
+//! cargo run --example map-reduce -- reduce --db-dir data-mapped.rocksdb --output-db-dir data-reduced.new --swap-into data-reduced.rocksdb
 //! ```
 //!
-//! Map step: (key, value) -> (value + '\0' + hex(key), key).
-//! Reduce step: group by value (strip the '\0' + hex(key) suffix) and join grouped keys with '|'.
+//! The steps themselves are `run_map` and `run_reduce` in the library's `mapreduce` module.
+//! Map step: (key, value) -> (value + sep + hex(key), key).
+//! Reduce step: group by value (strip the sep + hex(key) suffix) and join grouped keys with '|'.
+//! The separator is `--sep` (default 0x00), which both steps must agree on. Reduce splits at its last occurrence,
+//! since hex(key) never contains it (a hex digit is rejected as separator) while a transformed value might.
+//! With `--keep-value` on both steps, the map output's value holds the original key and value, encoded with
+//! `encode_composite_key` so neither can be confused for the other, and reduce joins `key=value` pairs instead.
+//! This keeps the original values available to the reduce step for transforms that don't group on them.
+//! The output DB is opened as ephemeral, with no WAL writes at all: if a step fails, rerun it from its input.
+//! Both steps shard the keyspace by the first `--prefix-len` hex chars (default 3, i.e. 4096 shards), one rayon task
+//! and one WriteBatch per shard. Fewer shards suit small DBs, more spread a huge one over more, smaller tasks: each
+//! shard's whole output is buffered in its batch before the write, so memory per task grows with shard density.
+//! Pass the same `--prefix-len` to both steps of a run. Reduce shards the map output keys, which start with the
+//! original values, so keep it at most the values' length: keys of shorter values would fall in no shard and be skipped.
+//! The output DB must be empty, since a step appending to a previous run's output would produce garbage;
+//! `--overwrite` deletes an existing one first.
+//! Both steps end with a compaction; `--bottommost` controls whether it rewrites the bottommost level (default force-optimized).
+//! The wall-clock time of each phase (open, map or reduce, flush, compaction) is printed at the end.
+//! The compaction shows a progress bar of the estimated bytes left to compact, see `compact_range_with_progress`.
+//! `--compression` sets the output DB's compression on every level, e.g. `--compression zstd` for reduce output,
+//! whose joined key lists compress well, and `--zstd-dict-bytes 65536` adds a trained dictionary to Zstd, which
+//! helps short, repetitive values like hex strings (see `open_rocksdb_for_bulk_ingestion`).
+//! The output's on-disk size is printed before and after the compaction, the difference being what compaction
+//! reclaimed from the flushed output, and the size after is the one to compare.
+//! `--swap-into` replaces a served DB with the fresh output once the step has succeeded, with `swap_db_dirs`:
+//! build into a dir next to the served one, so both are on the same filesystem and each rename is atomic.
 
 use anyhow::Result;
 use clap::Parser;
-use rayon::prelude::*;
+use rocksdb_examples::mapreduce::{run_map, run_reduce};
 use rocksdb_examples::rocksdb_utils::{
-    open_rocksdb_for_bulk_ingestion, open_rocksdb_for_read_only,
+    BottommostCompaction, BulkOpenOptions, Compression, FlushOnDrop, ReadOnlyOpenOptions,
+    compact_range_with_progress, destroy_rocksdb, open_rocksdb_for_bulk_ingestion,
+    open_rocksdb_for_read_only, swap_db_dirs, timed_open,
 };
-use rocksdb_examples::utils::{generate_consecutive_hex_strings, make_progress_bar};
-use rust_rocksdb::{Direction, IteratorMode};
+use rocksdb_examples::utils::{PhaseTimer, make_progress_bar, parse_separator};
+use rust_rocksdb::IteratorMode;
 
 const ROCKSDB_NUM_LEVELS: i32 = 7;
 
 #[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
 struct Cli {
-    /// Step to run (map, reduce)
-    step: String,
+    /// Step to run
+    #[clap(value_enum)]
+    step: Step,
     #[clap(long)]
     db_dir: String,
     #[clap(long)]
     output_db_dir: String,
+    /// Bottommost level compaction; use skip for already-compacted data that only needs a level change
+    #[clap(long, value_enum, default_value_t)]
+    bottommost: BottommostCompaction,
+    /// Map: keep the original value along with the key. Reduce: decode such map output
+    #[clap(long)]
+    keep_value: bool,
+    /// Byte between the value and hex(key) in map output keys, as one ASCII char or 0x-prefixed byte
+    #[clap(long, default_value = "0x00", value_parser = parse_separator)]
+    sep: u8,
+    /// Compression of the output DB on every level; default is Lz4 with Zstd on the bottommost level
+    #[clap(long, value_enum)]
+    compression: Option<Compression>,
+    /// Zstd dictionary size in bytes for the bottommost level, or every level with --compression zstd
+    #[clap(long)]
+    zstd_dict_bytes: Option<i32>,
+    /// Number of leading hex chars that shard the keyspace, 16^n shards (1 to 6)
+    #[clap(long, default_value_t = 3)]
+    prefix_len: u32,
+    /// Delete the output DB first if it already has data, instead of refusing to run
+    #[clap(long)]
+    overwrite: bool,
+    /// After a successful step, move the output DB to this path, replacing the DB there (see swap_db_dirs)
+    #[clap(long)]
+    swap_into: Option<String>,
+}
+
+/// The map-reduce step to run; parsed before anything is opened, so a typo can't touch the output DB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum Step {
+    Map,
+    Reduce,
 }
 
 fn main() -> Result<()> {
     let args = Cli::parse();
-    let db = open_rocksdb_for_read_only(&args.db_dir, true)?;
-    let output_db =
-        open_rocksdb_for_bulk_ingestion(&args.output_db_dir, Some(ROCKSDB_NUM_LEVELS), None)?;
-
-    match args.step.as_str() {
-        "map" => {
-            let prefixes = generate_consecutive_hex_strings(3);
-            let pb = make_progress_bar(Some(prefixes.len() as u64));
-
-            let count = prefixes
-                .into_par_iter()
-                .map(|prefix| {
-                    let prefix = prefix.as_bytes();
-                    let mut db_iter =
-                        db.full_iterator(IteratorMode::From(prefix, Direction::Forward));
-                    let mut count = 0;
-                    let mut write_batch = rust_rocksdb::WriteBatch::default();
-                    while let Some(item) = db_iter.next() {
-                        let (key, value) = item.unwrap();
-                        if &key[..prefix.len()] != prefix {
-                            break;
-                        }
-
-                        let key_hex = hex::encode(key.as_ref());
-                        let new_key: Vec<u8> = value
-                            .iter()
-                            .chain(std::iter::once(&0u8))
-                            .chain(key_hex.as_bytes())
-                            .cloned()
-                            .collect();
-                        let new_value = key;
-
-                        write_batch.put(&new_key, &new_value);
-                        count += 1;
-                    }
-                    output_db.write_without_wal(&write_batch).unwrap();
-                    pb.inc(1);
-                    count
-                })
-                .reduce(|| 0_usize, |acc, c| acc + c);
-
+    if args.sep.is_ascii_hexdigit() {
+        anyhow::bail!(
+            "--sep {:?} is a hex digit, so it could appear in hex(key)",
+            args.sep as char
+        );
+    }
+    if !(1..=6).contains(&args.prefix_len) {
+        anyhow::bail!("--prefix-len must be between 1 and 6");
+    }
+    if args.swap_into.as_deref() == Some(args.output_db_dir.as_str()) {
+        anyhow::bail!("--swap-into must differ from --output-db-dir");
+    }
+    let mut timer = PhaseTimer::new();
+    timer.start("open");
+    let db = timed_open(&args.db_dir, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+    let open_output_db = || {
+        open_rocksdb_for_bulk_ingestion(
+            &args.output_db_dir,
+            BulkOpenOptions {
+                num_levels: Some(ROCKSDB_NUM_LEVELS),
+                // the output is rebuilt from the input DB on failure, so it doesn't need a WAL
+                ephemeral: true,
+                compression: args.compression,
+                zstd_dict_bytes: args.zstd_dict_bytes,
+                ..Default::default()
+            },
+        )
+    };
+    let mut raw_output_db = open_output_db()?;
+    // writing into a previous run's output would mix both runs' entries
+    if raw_output_db.iterator(IteratorMode::Start).next().is_some() {
+        if !args.overwrite {
+            anyhow::bail!(
+                "output DB {} is not empty, pass --overwrite to delete it and start over",
+                args.output_db_dir
+            );
+        }
+        println!("Deleting the existing output DB {}", args.output_db_dir);
+        drop(raw_output_db);
+        destroy_rocksdb(&args.output_db_dir)?;
+        raw_output_db = open_output_db()?;
+    }
+    // writes skip the WAL, so flush them even if a step errors out or panics before its flush
+    let output_db = FlushOnDrop(&raw_output_db);
+
+    timer.start(match args.step {
+        Step::Map => "map",
+        Step::Reduce => "reduce",
+    });
+    let pb = make_progress_bar(Some(16_u64.pow(args.prefix_len)));
+    match args.step {
+        Step::Map => {
+            let count = run_map(
+                &db,
+                &output_db,
+                args.prefix_len,
+                args.sep,
+                args.keep_value,
+                &pb,
+            )?;
+
+            timer.start("flush");
             output_db.flush()?;
 
             pb.finish_with_message("done");
             println!("Count: {}", count);
         }
-        "reduce" => {
-            let prefixes = generate_consecutive_hex_strings(3);
-            let pb = make_progress_bar(Some(prefixes.len() as u64));
-
-            let counts = prefixes
-                .into_par_iter()
-                .map(|prefix| {
-                    let prefix = prefix.as_bytes();
-                    let mut db_iter =
-                        db.full_iterator(IteratorMode::From(prefix, Direction::Forward));
-                    let mut write_batch = rust_rocksdb::WriteBatch::default();
-                    let mut count = 0;
-                    let mut count_grouped = 0;
-                    let mut prev_key = Vec::<u8>::new();
-                    let mut blobs_vec: Vec<Vec<u8>> = vec![];
-                    while let Some(item) = db_iter.next() {
-                        let (key, value) = item.unwrap();
-                        if &key[..prefix.len()] != prefix {
-                            break;
-                        }
-
-                        // key is value + '\0' + key_hex; group by value = everything before first '\0'
-                        let sep = key.iter().position(|&b| b == 0).unwrap_or_else(|| {
-                            panic!("Invalid key: {}", String::from_utf8_lossy(&key))
-                        });
-                        let new_key = key[..sep].to_vec();
-
-                        if new_key != prev_key {
-                            if !prev_key.is_empty() {
-                                // concatenate with '|'
-                                // can use protobuf or anything else to serialize
-                                let new_value: Vec<u8> = blobs_vec.join(&b"|"[..]);
-                                write_batch.put(prev_key, new_value);
-                                count_grouped += 1;
-                            }
-                            blobs_vec = vec![];
-                            prev_key = new_key;
-                        }
-
-                        blobs_vec.push(value.to_vec());
-                        count += 1;
-                    }
-
-                    if !blobs_vec.is_empty() {
-                        let new_value: Vec<u8> = blobs_vec.join(&b"|"[..]);
-                        write_batch.put(prev_key, new_value);
-                        count_grouped += 1;
-                    }
-                    output_db.write_without_wal(&write_batch).unwrap();
-                    pb.inc(1);
-                    (count, count_grouped)
-                })
-                .reduce(
-                    || (0_usize, 0_usize),
-                    |accs, counts| (accs.0 + counts.0, accs.1 + counts.1),
-                );
-
+        Step::Reduce => {
+            let counts = run_reduce(
+                &db,
+                &output_db,
+                args.prefix_len,
+                args.sep,
+                args.keep_value,
+                &pb,
+            )?;
+
+            timer.start("flush");
             output_db.flush()?;
 
             pb.finish_with_message("done");
             println!("Count: {} count_grouped: {}", counts.0, counts.1);
         }
-        _ => {
-            panic!("Invalid step");
-        }
     }
 
     // Compaction
+    timer.start("compaction");
     println!("========== Compacting ==========");
+    // the flushed step output, before grouping and overwritten keys are compacted away
+    let size_before = output_db
+        .property_int_value("rocksdb.live-sst-files-size")?
+        .unwrap_or(0);
     let mut compaction_opts = rust_rocksdb::CompactOptions::default();
     compaction_opts.set_exclusive_manual_compaction(true);
     compaction_opts.set_change_level(true);
     compaction_opts.set_target_level(ROCKSDB_NUM_LEVELS - 1);
-    compaction_opts
-        .set_bottommost_level_compaction(rust_rocksdb::BottommostLevelCompaction::ForceOptimized);
-    output_db.compact_range_opt(None::<&[u8]>, None::<&[u8]>, &compaction_opts);
+    compaction_opts.set_bottommost_level_compaction(args.bottommost.into());
+    compact_range_with_progress(&output_db, None, None, &compaction_opts);
+    timer.report();
+
+    let size_after = output_db
+        .property_int_value("rocksdb.live-sst-files-size")?
+        .unwrap_or(0);
+    println!(
+        "Output DB size on disk: {} bytes before compaction, {} bytes after ({:.1}% of before)",
+        size_before,
+        size_after,
+        size_after as f64 / size_before.max(1) as f64 * 100.0
+    );
+
+    if let Some(live_dir) = &args.swap_into {
+        // both handles must be closed before the DB dir is renamed
+        drop(output_db);
+        drop(raw_output_db);
+        swap_db_dirs(&args.output_db_dir, live_dir)?;
+        println!("Swapped {} into {}", args.output_db_dir, live_dir);
+    }
 
     Ok(())
 }
@@ -0,0 +1,84 @@
+//! Concurrent counters with a merge operator.
+//!
+//! Usage:
+//! ```
+//! cargo run --example merge-counter -- --db-dir data-counter.rocksdb
+//! cargo run --example merge-counter -- --db-dir data-counter.rocksdb --num-keys 4 --num-merges 1000000
+//! ```
+//!
+//! This will open the DB with the `add_u64` merge operator and increment `--num-keys` counters `--num-merges` times
+//! in total, spread over rayon's default thread pool (RAYON_NUM_THREADS), each with `db.merge(key, 1u64)`.
+//! A merge only records the increment, without reading the counter, so threads hitting the same key don't need
+//! a transaction or a lock the way a read-modify-write put would. The increments are summed when a counter is read,
+//! and folded into one value by compaction.
+//! Then it reads every counter back and checks that their increase since the start adds up to the number of merges.
+//! Counters persist across runs, so rerunning keeps counting up.
+
+use anyhow::Result;
+use clap::Parser;
+use rayon::prelude::*;
+use rocksdb_examples::rocksdb_utils::open_rocksdb_for_counters;
+use rust_rocksdb::DB;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Number of counters the merges are spread over
+    #[arg(long, default_value_t = 16)]
+    num_keys: u64,
+    #[arg(long, default_value_t = 100_000)]
+    num_merges: u64,
+}
+
+fn counter_key(i: u64) -> String {
+    format!("counter-{:04}", i)
+}
+
+fn read_counter(db: &DB, key: &str) -> Result<u64> {
+    match db.get_pinned(key.as_bytes())? {
+        Some(value) => Ok(u64::from_le_bytes(value.as_ref().try_into()?)),
+        None => Ok(0),
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    if args.num_keys == 0 {
+        anyhow::bail!("--num-keys must be at least 1");
+    }
+    let db = open_rocksdb_for_counters(&args.db_dir)?;
+    let before = (0..args.num_keys)
+        .map(|i| read_counter(&db, &counter_key(i)))
+        .collect::<Result<Vec<u64>>>()?;
+
+    let start = Instant::now();
+    (0..args.num_merges)
+        .into_par_iter()
+        .try_for_each(|i| db.merge(counter_key(i % args.num_keys), 1_u64.to_le_bytes()))?;
+    println!(
+        "Merged {} increments into {} counters in {:.2?}",
+        args.num_merges,
+        args.num_keys,
+        start.elapsed()
+    );
+
+    let mut total = 0;
+    for (i, before) in before.iter().enumerate() {
+        let key = counter_key(i as u64);
+        let after = read_counter(&db, &key)?;
+        println!("{}: {} (+{})", key, after, after - before);
+        total += after - before;
+    }
+    if total != args.num_merges {
+        anyhow::bail!(
+            "counters increased by {} in total, expected {}",
+            total,
+            args.num_merges
+        );
+    }
+    println!("Counters increased by {} in total, as expected", total);
+    Ok(())
+}
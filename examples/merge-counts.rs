@@ -0,0 +1,108 @@
+//! Merge the partial counts of a parallel scan spread over several machines.
+//!
+//! Usage:
+//! ```
+//! cargo run --example merge-counts -- --in count0.json --in count1.json --in count2.json --in count3.json
+//! ```
+//!
+//! This will read the JSON summaries written by `parallel-scan --summary` on each machine and print the total count.
+//! All summaries must come from the same scan: the same `num_machines`, iter mode and `num_shards`, since machines
+//! with different settings would slice the keyspace differently. Every machine index in `0..num_machines` must
+//! appear exactly once, so the slices are disjoint and cover the whole keyspace; a missing or duplicated machine
+//! is an error naming it, since the sum would silently be wrong.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use serde_json::Value;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    /// Summary file of one machine, repeated for each machine
+    #[arg(long = "in", required = true)]
+    inputs: Vec<String>,
+}
+
+/// The fields of a summary that must match across machines of one scan.
+fn scan_settings(summary: &Value) -> (Value, Value, Value) {
+    (
+        summary["num_machines"].clone(),
+        summary["iter_mode"].clone(),
+        summary["num_shards"].clone(),
+    )
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+
+    let mut settings = None;
+    // input path per machine index, to report duplicates and missing machines
+    let mut machines: Vec<Option<&str>> = vec![];
+    let mut total = 0;
+    for input in &args.inputs {
+        let summary: Value = serde_json::from_str(
+            &std::fs::read_to_string(input).with_context(|| format!("reading {}", input))?,
+        )
+        .with_context(|| format!("parsing {}", input))?;
+        let field = |name: &str| {
+            summary[name]
+                .as_u64()
+                .with_context(|| format!("{} has no integer {:?}", input, name))
+        };
+        let (machine, num_machines, count) = (
+            field("machine")? as usize,
+            field("num_machines")? as usize,
+            field("count")?,
+        );
+
+        let this_settings = scan_settings(&summary);
+        if let Some(settings) = &settings {
+            if *settings != this_settings {
+                anyhow::bail!(
+                    "{} is from a different scan: (num_machines, iter_mode, num_shards) {:?}, expected {:?}",
+                    input,
+                    this_settings,
+                    settings
+                );
+            }
+        } else {
+            settings = Some(this_settings);
+            machines = vec![None; num_machines];
+        }
+        if machine >= num_machines {
+            anyhow::bail!(
+                "{}: machine {} out of range 0..{}",
+                input,
+                machine,
+                num_machines
+            );
+        }
+        if let Some(other) = machines[machine] {
+            anyhow::bail!(
+                "machine {} is duplicated in {} and {}",
+                machine,
+                other,
+                input
+            );
+        }
+        machines[machine] = Some(input.as_str());
+        total += count;
+        println!("machine {}: {} ({})", machine, count, input);
+    }
+
+    let missing: Vec<usize> = machines
+        .iter()
+        .enumerate()
+        .filter(|(_, input)| input.is_none())
+        .map(|(machine, _)| machine)
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "missing summaries for machines {:?} of {}",
+            missing,
+            machines.len()
+        );
+    }
+    println!("Count: {}", total);
+    Ok(())
+}
@@ -3,49 +3,293 @@
 //! Usage:
 //! ```
 //! cargo run --example parallel_scan -- --db-dir data.rocksdb
+//! cargo run --example parallel_scan -- --db-dir data.rocksdb --num-shards 64
+//! cargo run --example parallel_scan -- --db-dir data.rocksdb --iter-mode prefix
+//! cargo run --example parallel_scan -- --db-dir data.rocksdb --iter-mode full --reverse
+//! cargo run --example parallel_scan -- --db-dir data.rocksdb --iter-mode prefix --machine 0 --num-machines 4
+//! cargo run --example parallel_scan -- --db-dir data.rocksdb --machine 0 --num-machines 4 --num-shards 64 --summary count0.json
 //! ```
 //!
 //! This will scan the DB for all keys in each DB.
-//! Parallelized by rayon's default thread pool (RAYON_NUM_THREADS); each thread scans the DB for keys that start with the first 4 characters of the hex string.
+//! Parallelized by rayon's default thread pool (RAYON_NUM_THREADS); by default the keys are counted with `par_scan`,
+//! which scans up to NUM_SHARDS key ranges of roughly equal on-disk size, one per thread.
+//! With `--iter-mode full` or `--iter-mode prefix`, each thread instead scans the keys under one PREFIX_LEN-hex-char
+//! prefix, with `full_iterator` seeking to the prefix or with `prefix_iterator`, to measure the prefix bloom speedup.
+//! For `--iter-mode prefix` the DB is opened with a fixed PREFIX_LEN prefix extractor
+//! (`open_rocksdb_for_read_only_with_prefix`), so `prefix_iterator` stops at the prefix end by itself, and uses prefix
+//! blooms if the DB was also written with a matching one. `full_iterator` has no such bound, so `--iter-mode full`
+//! stops at the first key outside the prefix, which costs reading one key past it.
+//! With `--reverse`, each shard is visited in descending key order instead, for consumers that stream keys from the
+//! top: a reverse iterator bounded to the shard's range (or to the prefix and `next_prefix` of it) starts from its
+//! last key. The count is the same as forward. `prefix_iterator` only goes forward, so `--iter-mode prefix`
+//! can't be reversed.
+//! With `--slowest K`, each shard is scanned by its own task and timed, and the K slowest shards are printed at the end
+//! with their range or prefix, key count and elapsed time. A shard far slower than the rest usually holds far more keys,
+//! pointing at data skew. `par_scan` doesn't tell the shards apart, so it's only used for a plain forward range scan
+//! on one machine without `--slowest`.
+//! To spread a scan over several machines, run it on each with `--machine i --num-machines k`: each one only scans
+//! the shards whose index is i mod k and prints its partial count, and the partial counts sum to the total.
+//! In range mode, all machines must pass the same `--num-shards` so they split the DB into the same ranges.
+//! `--summary` also writes the partial count as JSON, with the machine index and settings; merge-counts sums the
+//! summaries of all machines and checks that none is missing or duplicated.
 
 use anyhow::Result;
 use clap::Parser;
+use indicatif::ProgressBar;
+use rayon::iter::Either;
 use rayon::prelude::*;
-use rocksdb_examples::rocksdb_utils::open_rocksdb_for_read_only;
-use rocksdb_examples::utils::{generate_consecutive_hex_strings, make_progress_bar};
-use rust_rocksdb::{Direction, IteratorMode};
+use rocksdb_examples::parscan::{par_scan, range_iterator, range_iterator_reverse};
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, next_prefix, open_rocksdb_for_read_only,
+    open_rocksdb_for_read_only_with_prefix, split_key_ranges_by_size, timed_open,
+};
+use rocksdb_examples::utils::{
+    generate_consecutive_hex_strings, make_progress_bar, write_file_atomically,
+};
+use rust_rocksdb::{DB, DBIterator, Direction, IteratorMode};
+use std::time::{Duration, Instant};
+
+const PREFIX_LEN: u32 = 3;
+
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+enum IterMode {
+    /// Bounded iterators over key ranges of roughly equal size
+    #[default]
+    Range,
+    /// full_iterator from each hex prefix
+    Full,
+    /// prefix_iterator for each hex prefix
+    Prefix,
+}
 
 #[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
 struct Cli {
     #[arg(long)]
     db_dir: String,
+    /// With --iter-mode range, number of key ranges to split the scan into (defaults to the number of rayon threads)
+    #[arg(long)]
+    num_shards: Option<usize>,
+    /// Approximate number of keys, to show a progress bar with ETA (e.g. from inspect-rocksdb --count) instead of a spinner
+    #[arg(long)]
+    progress_total: Option<u64>,
+    /// How each thread iterates its shard
+    #[arg(long, value_enum, default_value_t)]
+    iter_mode: IterMode,
+    /// Number of slowest shards to print with their elapsed time (0 skips the per-shard timing)
+    #[arg(long, default_value_t = 0)]
+    slowest: usize,
+    /// Index of this machine, in 0..num_machines, to scan only its slice of the shards
+    #[arg(long, default_value_t = 0)]
+    machine: usize,
+    /// Number of machines the scan is spread over
+    #[arg(long, default_value_t = 1)]
+    num_machines: usize,
+    /// Write the (partial) count and the scan settings to this path as JSON, for merge-counts
+    #[arg(long)]
+    summary: Option<String>,
+    /// Visit the keys of each shard in descending order
+    #[arg(long)]
+    reverse: bool,
+}
+
+/// A shard's label (its key range or prefix), key count and elapsed time.
+type ShardTiming = (String, usize, Duration);
+
+/// Keep the `k` slowest shards, slowest first.
+fn keep_slowest(mut shards: Vec<ShardTiming>, k: usize) -> Vec<ShardTiming> {
+    shards.sort_by_key(|shard| std::cmp::Reverse(shard.2));
+    shards.truncate(k);
+    shards
+}
+
+/// Count the keys under `prefix` with the iterator of `iter_mode`, in descending order if `reverse`, timing the scan.
+fn scan_prefix(
+    db: &DB,
+    prefix: &str,
+    iter_mode: IterMode,
+    reverse: bool,
+    pb: &ProgressBar,
+) -> Result<ShardTiming> {
+    let start = Instant::now();
+    let prefix_bytes = prefix.as_bytes();
+    let db_iter: DBIterator = match iter_mode {
+        IterMode::Prefix => db.prefix_iterator(prefix_bytes),
+        _ if reverse => {
+            range_iterator_reverse(db, Some(prefix_bytes.to_vec()), next_prefix(prefix_bytes))
+        }
+        _ => db.full_iterator(IteratorMode::From(prefix_bytes, Direction::Forward)),
+    };
+    // prefix_iterator (with the prefix extractor) and the reverse range are bounded, full_iterator isn't
+    let bounded = reverse || matches!(iter_mode, IterMode::Prefix);
+    let mut count = 0;
+    for item in db_iter {
+        let (key, _value) = item?;
+        if !bounded && !key.starts_with(prefix_bytes) {
+            break;
+        }
+        count += 1;
+        pb.inc(1);
+    }
+    Ok((prefix.to_string(), count, start.elapsed()))
+}
+
+/// Count the keys in `[lower, upper)`, in descending order if `reverse`, timing the scan.
+fn scan_range(
+    db: &DB,
+    lower: Option<Vec<u8>>,
+    upper: Option<Vec<u8>>,
+    reverse: bool,
+    pb: &ProgressBar,
+) -> Result<ShardTiming> {
+    let start = Instant::now();
+    let label = format!(
+        "[{}, {})",
+        String::from_utf8_lossy(lower.as_deref().unwrap_or_default()),
+        String::from_utf8_lossy(upper.as_deref().unwrap_or_default())
+    );
+    let db_iter = if reverse {
+        range_iterator_reverse(db, lower, upper)
+    } else {
+        range_iterator(db, lower, upper)
+    };
+    let mut count = 0;
+    for item in db_iter {
+        item?;
+        count += 1;
+        pb.inc(1);
+    }
+    Ok((label, count, start.elapsed()))
+}
+
+/// The slice of `shards` for `machine` out of `num_machines`: every shard whose index is `machine` mod `num_machines`.
+/// Each index has exactly one remainder, so the slices of all machines are disjoint and cover every shard.
+fn machine_slice<T>(shards: Vec<T>, machine: usize, num_machines: usize) -> Vec<T> {
+    shards
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| i % num_machines == machine)
+        .map(|(_, shard)| shard)
+        .collect()
 }
 
 fn main() -> Result<()> {
     let args = Cli::parse();
-    let db = open_rocksdb_for_read_only(&args.db_dir, true)?;
-
-    let prefixes = generate_consecutive_hex_strings(3);
-    let pb = make_progress_bar(Some(prefixes.len() as u64));
-
-    let count = prefixes
-        .into_par_iter()
-        .map(|prefix| {
-            let prefix = prefix.as_bytes();
-            let mut db_iter = db.full_iterator(IteratorMode::From(prefix, Direction::Forward));
-            let mut count = 0;
-            while let Some(Ok((key, _value))) = db_iter.next() {
-                if &key[..prefix.len()] != prefix {
-                    break;
-                }
-                count += 1;
-            }
-            pb.inc(1);
-            count
-        })
-        .reduce(|| 0_usize, |acc, c| acc + c);
+    if args.num_machines == 0 || args.machine >= args.num_machines {
+        anyhow::bail!(
+            "--machine must be in 0..{} (--num-machines), got {}",
+            args.num_machines,
+            args.machine
+        );
+    }
+    if args.num_machines > 1
+        && matches!(args.iter_mode, IterMode::Range)
+        && args.num_shards.is_none()
+    {
+        // the default depends on each machine's thread count, so machines would split different ranges
+        anyhow::bail!(
+            "--num-machines with --iter-mode range needs the same explicit --num-shards on every machine"
+        );
+    }
+    if args.reverse && matches!(args.iter_mode, IterMode::Prefix) {
+        anyhow::bail!(
+            "--reverse can't be combined with --iter-mode prefix, prefix_iterator only goes forward"
+        );
+    }
+    let db = timed_open(&args.db_dir, || match args.iter_mode {
+        IterMode::Prefix => {
+            open_rocksdb_for_read_only_with_prefix(&args.db_dir, PREFIX_LEN as usize)
+        }
+        _ => open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        ),
+    })?;
 
+    let num_shards = args.num_shards.unwrap_or(rayon::current_num_threads());
+    let pb = make_progress_bar(args.progress_total);
+
+    if matches!(args.iter_mode, IterMode::Range)
+        && !args.reverse
+        && args.num_machines == 1
+        && args.slowest == 0
+    {
+        let count = par_scan(&db, num_shards)
+            .map(|item| {
+                item?;
+                pb.inc(1);
+                Ok(1)
+            })
+            .sum::<Result<usize>>()?;
+        pb.finish_with_message("done");
+        println!("Count: {}", count);
+        return write_summary(&args, count);
+    }
+
+    let prefixes = generate_consecutive_hex_strings(PREFIX_LEN);
+    let shards = match args.iter_mode {
+        IterMode::Range => Either::Left(
+            machine_slice(
+                split_key_ranges_by_size(&db, &prefixes, num_shards.clamp(1, prefixes.len())),
+                args.machine,
+                args.num_machines,
+            )
+            .into_par_iter()
+            .map(|(lower, upper)| scan_range(&db, lower, upper, args.reverse, &pb)),
+        ),
+        IterMode::Full | IterMode::Prefix => Either::Right(
+            machine_slice(prefixes, args.machine, args.num_machines)
+                .into_par_iter()
+                .map(|prefix| scan_prefix(&db, &prefix, args.iter_mode, args.reverse, &pb)),
+        ),
+    };
+    // total count and the slowest shards so far, trimmed to --slowest at every merge
+    let (count, slowest) = shards
+        .map(|timing| timing.map(|timing| (timing.1, vec![timing])))
+        .try_reduce(
+            || (0_usize, vec![]),
+            |(count, mut slowest), (other_count, other)| {
+                slowest.extend(other);
+                Ok((count + other_count, keep_slowest(slowest, args.slowest)))
+            },
+        )?;
     pb.finish_with_message("done");
-    println!("Count: {}", count);
+
+    if args.num_machines > 1 {
+        println!(
+            "Partial count of machine {} of {}: {}",
+            args.machine, args.num_machines, count
+        );
+    } else {
+        println!("Count: {}", count);
+    }
+    if args.slowest > 0 {
+        println!("Slowest {} shards:", slowest.len());
+        for (label, count, elapsed) in &slowest {
+            println!("{:>12.2?} {:>12} keys  {}", elapsed, count, label);
+        }
+    }
+    write_summary(&args, count)
+}
+
+/// Write the (partial) `count` and the scan settings to `--summary`, if given.
+fn write_summary(args: &Cli, count: usize) -> Result<()> {
+    if let Some(summary) = &args.summary {
+        let contents = serde_json::json!({
+            "machine": args.machine,
+            "num_machines": args.num_machines,
+            "iter_mode": format!("{:?}", args.iter_mode).to_lowercase(),
+            "num_shards": args.num_shards,
+            "count": count,
+        });
+        write_file_atomically(
+            summary,
+            &format!("{}\n", serde_json::to_string_pretty(&contents)?),
+        )?;
+        println!("Wrote summary to {}", summary);
+    }
     Ok(())
 }
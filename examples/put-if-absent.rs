@@ -0,0 +1,54 @@
+//! Concurrent put-if-absent on RocksDB.
+//!
+//! Usage:
+//! ```
+//! cargo run --example put-if-absent -- --db-dir data-txn.rocksdb
+//! ```
+//!
+//! This will open the DB as a TransactionDB and race NUM_THREADS threads doing `put_if_absent` on the same random key,
+//! each with its own value. Exactly one of them should insert; the others either find the key present, or time out
+//! waiting for the winner's lock, and count as not inserted instead of failing the run.
+//! Key and value are random raw bytes encoded as hex strings.
+
+use anyhow::Result;
+use clap::Parser;
+use rayon::prelude::*;
+use rocksdb_examples::rocksdb_utils::{open_rocksdb_for_transactions, put_if_absent};
+use rocksdb_examples::utils::generate_random_hex_string;
+
+const NUM_THREADS: usize = 8;
+const KEY_LEN: usize = 16;
+const VAL_LEN: usize = 3;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db = open_rocksdb_for_transactions(&args.db_dir, None, None)?;
+
+    let key = generate_random_hex_string(KEY_LEN);
+    let inserted = (0..NUM_THREADS)
+        .into_par_iter()
+        .map(|_| {
+            let val = generate_random_hex_string(VAL_LEN);
+            put_if_absent(&db, key.as_bytes(), val.as_bytes())
+        })
+        .collect::<Result<Vec<bool>>>()?;
+    let num_inserted = inserted.iter().filter(|&&inserted| inserted).count();
+
+    println!("key: {}", key);
+    let value = db
+        .get(key.as_bytes())?
+        .ok_or(anyhow::anyhow!("key not found"))?;
+    println!("val: {}", std::str::from_utf8(&value)?);
+    println!("Inserted by {} of {} threads", num_inserted, NUM_THREADS);
+    if num_inserted != 1 {
+        anyhow::bail!("expected exactly one insert, got {}", num_inserted);
+    }
+    Ok(())
+}
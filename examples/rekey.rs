@@ -0,0 +1,134 @@
+//! Copy RocksDB into a new DB with every key's prefix rewritten.
+//!
+//! Usage:
+//! ```
+//! cargo run --example rekey -- --db-dir data.rocksdb --out data-ns.rocksdb --add-prefix ns
+//! cargo run --example rekey -- --db-dir data-ns.rocksdb --out data.rocksdb --strip-prefix ns
+//! cargo run --example rekey -- --db-dir data.rocksdb --out data-v2.rocksdb --replace-prefix 00 ff --keep-unmatched
+//! ```
+//!
+//! This will scan the DB and write each entry, value unchanged, to a new bulk-loaded DB at OUT with its key
+//! transformed by one rule: `--add-prefix P` prepends P to every key, `--strip-prefix P` removes P from keys starting
+//! with it, and `--replace-prefix OLD NEW` swaps OLD for NEW in keys starting with OLD. Changing prefixes changes the
+//! sort order, so keys can't be renamed in place; the new DB is written in batches without WAL, then compacted.
+//! Keys that don't match a strip or replace rule are skipped, or with `--keep-unmatched`, copied unchanged (the
+//! DB's own keys then share the new keyspace, so a later key with the same bytes overwrites the earlier one).
+//! The number of keys transformed and skipped is printed.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    BulkOpenOptions, FlushOnDrop, ReadOnlyOpenOptions, open_rocksdb_for_bulk_ingestion,
+    open_rocksdb_for_read_only, timed_open,
+};
+use rocksdb_examples::utils::make_progress_bar;
+use rust_rocksdb::{IteratorMode, WriteBatch};
+
+const ROCKSDB_NUM_LEVELS: i32 = 7;
+const BATCH_SIZE: usize = 10_000;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+#[command(group(clap::ArgGroup::new("rule").required(true)))]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Directory of the new DB
+    #[arg(long)]
+    out: String,
+    /// Prepend this prefix to every key
+    #[arg(long, group = "rule")]
+    add_prefix: Option<String>,
+    /// Remove this prefix from the keys starting with it
+    #[arg(long, group = "rule")]
+    strip_prefix: Option<String>,
+    /// Replace the prefix OLD with NEW in the keys starting with OLD
+    #[arg(long, group = "rule", num_args = 2, value_names = ["OLD", "NEW"])]
+    replace_prefix: Option<Vec<String>>,
+    /// Copy keys that don't match --strip-prefix or --replace-prefix unchanged instead of skipping them
+    #[arg(long)]
+    keep_unmatched: bool,
+}
+
+/// The key rewritten by the chosen rule, or None if it doesn't match.
+fn rekey(args: &Cli, key: &[u8]) -> Option<Vec<u8>> {
+    if let Some(prefix) = &args.add_prefix {
+        Some([prefix.as_bytes(), key].concat())
+    } else if let Some(prefix) = &args.strip_prefix {
+        key.strip_prefix(prefix.as_bytes()).map(<[u8]>::to_vec)
+    } else if let Some([old, new]) = args.replace_prefix.as_deref() {
+        key.strip_prefix(old.as_bytes())
+            .map(|rest| [new.as_bytes(), rest].concat())
+    } else {
+        unreachable!("clap requires one rule")
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    if std::path::Path::new(&args.out) == std::path::Path::new(&args.db_dir) {
+        anyhow::bail!("--out must be a new DB, not --db-dir");
+    }
+    let db = timed_open(&args.db_dir, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+    let out_db = open_rocksdb_for_bulk_ingestion(
+        &args.out,
+        BulkOpenOptions {
+            num_levels: Some(ROCKSDB_NUM_LEVELS),
+            ..Default::default()
+        },
+    )?;
+    // writes skip the WAL, so flush them even if the run errors out before the flush below
+    let out_db = FlushOnDrop(&out_db);
+
+    let pb = make_progress_bar(None);
+    let mut count_transformed = 0;
+    let mut count_skipped = 0;
+    let mut write_batch = WriteBatch::default();
+    for item in db.full_iterator(IteratorMode::Start) {
+        let (key, value) = item?;
+        match rekey(&args, &key) {
+            Some(new_key) => {
+                write_batch.put(&new_key, &value);
+                count_transformed += 1;
+            }
+            None => {
+                if args.keep_unmatched {
+                    write_batch.put(&key, &value);
+                }
+                count_skipped += 1;
+            }
+        }
+        if write_batch.len() == BATCH_SIZE {
+            out_db.write_without_wal(&write_batch)?;
+            write_batch.clear();
+        }
+        pb.inc(1);
+    }
+    out_db.write_without_wal(&write_batch)?;
+    out_db.flush()?;
+    pb.finish_with_message("done");
+
+    let mut compaction_opts = rust_rocksdb::CompactOptions::default();
+    compaction_opts.set_exclusive_manual_compaction(true);
+    compaction_opts.set_change_level(true);
+    compaction_opts.set_target_level(ROCKSDB_NUM_LEVELS - 1);
+    compaction_opts
+        .set_bottommost_level_compaction(rust_rocksdb::BottommostLevelCompaction::ForceOptimized);
+    out_db.compact_range_opt(None::<&[u8]>, None::<&[u8]>, &compaction_opts);
+
+    println!("Transformed: {}", count_transformed);
+    if args.keep_unmatched {
+        println!("Unmatched, copied unchanged: {}", count_skipped);
+    } else {
+        println!("Unmatched, skipped: {}", count_skipped);
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! Scan RocksDB and report unreadable key ranges.
+//!
+//! Usage:
+//! ```
+//! cargo run --example scan-healthcheck -- --db-dir data.rocksdb
+//! ```
+//!
+//! This will iterate the whole DB with checksum verification on.
+//! Instead of aborting on the first error (e.g. a block checksum mismatch), it records the last good key,
+//! re-seeks past the bad spot and keeps going, then prints a report of the bad ranges.
+//! Re-seeking first tries the immediate successor of the last good key, then the next prefix of shorter and
+//! shorter truncations of it, so keys inside a reported range may be unreadable or simply skipped.
+//! Exits with a nonzero status if any errors were found.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, next_prefix, open_rocksdb_for_read_only, timed_open,
+};
+use rocksdb_examples::utils::make_progress_bar;
+use rust_rocksdb::{DBRawIterator, ReadOptions};
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Approximate number of keys, to show a progress bar with ETA (e.g. from inspect-rocksdb --count) instead of a spinner
+    #[arg(long)]
+    progress_total: Option<u64>,
+}
+
+struct BadRange {
+    last_good_key: Option<Vec<u8>>,
+    resumed_at: Option<Vec<u8>>,
+    error: String,
+}
+
+/// Re-seek the iterator past a bad spot right after `last_good_key` and return the key it resumed at.
+///
+/// Returns `None` if there is nothing readable left.
+fn skip_past_bad_spot(
+    db_iter: &mut DBRawIterator,
+    last_good_key: Option<&[u8]>,
+) -> Option<Vec<u8>> {
+    let mut candidates = vec![];
+    match last_good_key {
+        Some(key) => {
+            let mut successor = key.to_vec();
+            successor.push(0);
+            candidates.push(successor);
+            for len in (1..=key.len()).rev() {
+                candidates.extend(next_prefix(&key[..len]));
+            }
+        }
+        // the very first block is bad, so probe by leading byte
+        None => candidates.extend((1..=u8::MAX).map(|b| vec![b])),
+    }
+
+    for candidate in candidates {
+        db_iter.seek(&candidate);
+        if db_iter.valid() {
+            return db_iter.key().map(|key| key.to_vec());
+        }
+        if db_iter.status().is_ok() {
+            // reached the end of the DB cleanly
+            return None;
+        }
+    }
+    None
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db = timed_open(&args.db_dir, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+
+    let mut readopts = ReadOptions::default();
+    readopts.set_total_order_seek(true);
+    readopts.set_verify_checksums(true);
+    readopts.fill_cache(false);
+    let mut db_iter = db.raw_iterator_opt(readopts);
+
+    let pb = make_progress_bar(args.progress_total);
+    let mut count = 0;
+    let mut last_good_key: Option<Vec<u8>> = None;
+    let mut bad_ranges: Vec<BadRange> = vec![];
+
+    db_iter.seek_to_first();
+    loop {
+        while db_iter.valid() {
+            last_good_key = db_iter.key().map(|key| key.to_vec());
+            count += 1;
+            pb.inc(1);
+            db_iter.next();
+        }
+
+        let Err(e) = db_iter.status() else {
+            break;
+        };
+        let resumed_at = skip_past_bad_spot(&mut db_iter, last_good_key.as_deref());
+        let done = resumed_at.is_none();
+        bad_ranges.push(BadRange {
+            last_good_key: last_good_key.clone(),
+            resumed_at,
+            error: e.to_string(),
+        });
+        if done {
+            break;
+        }
+    }
+
+    pb.finish_with_message("done");
+    println!("Readable: {}", count);
+    println!("Bad ranges: {}", bad_ranges.len());
+    for bad_range in &bad_ranges {
+        let after = bad_range
+            .last_good_key
+            .as_deref()
+            .map(String::from_utf8_lossy)
+            .unwrap_or("<start>".into());
+        let until = bad_range
+            .resumed_at
+            .as_deref()
+            .map(String::from_utf8_lossy)
+            .unwrap_or("<end>".into());
+        println!("({after}, {until}): {}", bad_range.error);
+    }
+
+    if !bad_ranges.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
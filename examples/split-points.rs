@@ -0,0 +1,87 @@
+//! Precompute balanced split points and store them next to the DB.
+//!
+//! Usage:
+//! ```
+//! cargo run --example split-points -- --db-dir data.rocksdb --store --num-ranges 16
+//! cargo run --example split-points -- --db-dir data.rocksdb
+//! cargo run --example inspect-rocksdb -- --db-dir data.rocksdb --count --balanced --stored-split-points
+//! ```
+//!
+//! With `--store`, the keyspace is split into `--num-ranges` ranges of roughly equal on-disk size
+//! (see `split_key_ranges_by_size`) and the split points between them are written to a sidecar file next to the DB
+//! dir (`split_points_path`, e.g. `data.rocksdb.split-points`), one hex-encoded split point per line, so they never
+//! show up in scans of the DB. Only approximate sizes are read, so `--store` opens the DB read-only.
+//! Without `--store`, the stored split points and their ranges are printed.
+//! Downstream jobs read them back with `load_split_points` instead of recomputing approximate sizes on every run,
+//! so every run partitions the DB the same way. Store them again after large writes or compactions.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, key_ranges_from_split_points, load_split_points,
+    open_rocksdb_for_read_only, split_key_ranges_by_size, split_points_path, store_split_points,
+    timed_open,
+};
+use rocksdb_examples::utils::generate_consecutive_hex_strings;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Compute the split points and store them next to the DB, instead of printing the stored ones
+    #[arg(long)]
+    store: bool,
+    /// Number of ranges for --store (defaults to the number of rayon threads)
+    #[arg(long)]
+    num_ranges: Option<usize>,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+
+    if args.store {
+        let prefixes = generate_consecutive_hex_strings(3);
+        let num_ranges = args.num_ranges.unwrap_or(rayon::current_num_threads());
+        if num_ranges == 0 || num_ranges > prefixes.len() {
+            anyhow::bail!("--num-ranges must be between 1 and {}", prefixes.len());
+        }
+        let db = timed_open(&args.db_dir, || {
+            open_rocksdb_for_read_only(
+                &args.db_dir,
+                ReadOnlyOpenOptions {
+                    fast_open_for_iteration: true,
+                    ..Default::default()
+                },
+            )
+        })?;
+        let split_points: Vec<Vec<u8>> = split_key_ranges_by_size(&db, &prefixes, num_ranges)
+            .into_iter()
+            .filter_map(|(_, upper)| upper)
+            .collect();
+        store_split_points(&args.db_dir, &split_points)?;
+        println!(
+            "Stored {} split points ({} ranges) in {}",
+            split_points.len(),
+            split_points.len() + 1,
+            split_points_path(&args.db_dir)
+        );
+        return Ok(());
+    }
+
+    let Some(split_points) = load_split_points(&args.db_dir)? else {
+        anyhow::bail!("no split points stored, run with --store first");
+    };
+    for (lower, upper) in key_ranges_from_split_points(split_points) {
+        let lower = lower
+            .as_deref()
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+        let upper = upper
+            .as_deref()
+            .map(String::from_utf8_lossy)
+            .unwrap_or_default();
+        println!("[{lower}, {upper})");
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! Stress RocksDB with one writer and concurrent readers.
+//!
+//! Usage:
+//! ```
+//! cargo run --release --example stress -- --db-dir data.rocksdb --duration-secs 30 --readers 8
+//! ```
+//!
+//! This will open the DB for write and run, for `--duration-secs`, one writer thread putting entries with the WAL
+//! and `--readers` threads doing random gets, then print the write and read throughput and any errors.
+//! Keys are the writer's counter scrambled by a multiplication and encoded as hex strings, so writes land in
+//! random order, while readers can rebuild the key of any entry already written. A get that misses such a key
+//! is counted as a missing read, since a completed put must be visible to every later read.
+//! Throughput per second is printed every second, which makes write stalls visible as dips.
+//! `--max-background-jobs` sets how many flushes and compactions run concurrently; too few and compaction falls
+//! behind the writer, causing stalls, too many and they take CPU from the readers.
+
+use anyhow::Result;
+use clap::Parser;
+use rand::RngExt;
+use rocksdb_examples::rocksdb_utils::{WriteOpenOptions, open_rocksdb_for_write};
+use rocksdb_examples::utils::generate_random_hex_string;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+const VAL_LEN: usize = 100;
+// odd, so scrambling is a bijection on u64 and keys never collide
+const SCRAMBLE: u64 = 0x9e37_79b9_7f4a_7c15;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    #[arg(long, default_value_t = 10)]
+    duration_secs: u64,
+    /// Number of reader threads
+    #[arg(long, default_value_t = 4)]
+    readers: usize,
+    /// Max concurrent background flushes and compactions (defaults to num_cpus)
+    #[arg(long)]
+    max_background_jobs: Option<i32>,
+}
+
+fn key_of(i: u64) -> String {
+    format!("{:016x}", i.wrapping_mul(SCRAMBLE))
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db = open_rocksdb_for_write(
+        &args.db_dir,
+        WriteOpenOptions {
+            max_background_jobs: args.max_background_jobs,
+            ..Default::default()
+        },
+    )?;
+
+    let stop = AtomicBool::new(false);
+    // entries 0..num_written have been put, so they must be visible to readers
+    let num_written = AtomicU64::new(0);
+    let num_reads = AtomicU64::new(0);
+    let num_missing = AtomicU64::new(0);
+    let num_errors = AtomicU64::new(0);
+
+    let start = Instant::now();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let mut i = 0;
+            while !stop.load(Ordering::Relaxed) {
+                let val = generate_random_hex_string(VAL_LEN);
+                match db.put(key_of(i).as_bytes(), val.as_bytes()) {
+                    Ok(()) => {
+                        i += 1;
+                        num_written.store(i, Ordering::Release);
+                    }
+                    Err(e) => {
+                        eprintln!("write error: {}", e);
+                        num_errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        });
+
+        for _ in 0..args.readers {
+            s.spawn(|| {
+                let mut rng = rand::rng();
+                while !stop.load(Ordering::Relaxed) {
+                    let written = num_written.load(Ordering::Acquire);
+                    if written == 0 {
+                        std::thread::yield_now();
+                        continue;
+                    }
+                    match db.get_pinned(key_of(rng.random_range(0..written)).as_bytes()) {
+                        Ok(Some(_)) => {}
+                        Ok(None) => {
+                            num_missing.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(e) => {
+                            eprintln!("read error: {}", e);
+                            num_errors.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                    num_reads.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+
+        let (mut last_written, mut last_reads) = (0, 0);
+        for second in 1..=args.duration_secs {
+            std::thread::sleep(Duration::from_secs(1));
+            let written = num_written.load(Ordering::Relaxed);
+            let reads = num_reads.load(Ordering::Relaxed);
+            println!(
+                "{:>4}s writes/s: {:>9} reads/s: {:>9}",
+                second,
+                written - last_written,
+                reads - last_reads
+            );
+            (last_written, last_reads) = (written, reads);
+        }
+        stop.store(true, Ordering::Relaxed);
+    });
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let written = num_written.load(Ordering::Relaxed);
+    let reads = num_reads.load(Ordering::Relaxed);
+    println!("Writes: {} ({:.0}/s)", written, written as f64 / elapsed);
+    println!(
+        "Reads: {} ({:.0}/s) with {} readers",
+        reads,
+        reads as f64 / elapsed,
+        args.readers
+    );
+    println!("Missing reads: {}", num_missing.load(Ordering::Relaxed));
+    println!("Errors: {}", num_errors.load(Ordering::Relaxed));
+    Ok(())
+}
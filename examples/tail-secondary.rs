@@ -0,0 +1,89 @@
+//! Follow a DB that another process is writing to, with a secondary instance.
+//!
+//! Usage:
+//! ```
+//! cargo run --example tail-secondary -- --db-dir data.rocksdb
+//! cargo run --example tail-secondary -- --db-dir data.rocksdb --secondary-dir data.secondary --exact --iterations 60
+//! ```
+//!
+//! This will open the DB as a secondary instance and, every `--interval-secs`, catch up with the primary and print
+//! the key count and how much it changed since the last catch-up, e.g. while write-hex-hashes or stress runs.
+//! The count is RocksDB's `rocksdb.estimate-num-keys`, which is cheap but approximate: it counts overwrites and
+//! deletes as entries until compaction drops them. `--exact` counts with a full scan instead, which reads the whole
+//! DB on every catch-up. Reads between catch-ups see the DB as of the last one, never a half-applied write batch.
+//! The secondary keeps its own info logs in `--secondary-dir`, created if missing, or in a per-process temp dir
+//! that's removed on exit. It runs until interrupted, or for `--iterations` catch-ups.
+
+use anyhow::Result;
+use clap::Parser;
+use rocksdb_examples::rocksdb_utils::{
+    catch_up, collect_rocksdb_stats, open_rocksdb_as_secondary, timed_open,
+};
+use rocksdb_examples::utils::ScratchDir;
+use rust_rocksdb::{DB, IteratorMode};
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Directory for the secondary's info logs, unique per secondary (defaults to a temp dir removed on exit)
+    #[arg(long)]
+    secondary_dir: Option<String>,
+    #[arg(long, default_value_t = 1)]
+    interval_secs: u64,
+    /// Number of catch-ups before exiting (defaults to running until interrupted)
+    #[arg(long)]
+    iterations: Option<u64>,
+    /// Count keys with a full scan instead of estimating
+    #[arg(long)]
+    exact: bool,
+}
+
+fn count_keys(db: &DB, exact: bool) -> Result<u64> {
+    if !exact {
+        return Ok(collect_rocksdb_stats(db)?.num_keys_estimate.unwrap_or(0));
+    }
+    let mut count = 0;
+    for item in db.iterator(IteratorMode::Start) {
+        item?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let scratch_dir = match &args.secondary_dir {
+        Some(_) => None,
+        None => Some(ScratchDir::create(None, "tail-secondary")?),
+    };
+    let secondary_dir = match &scratch_dir {
+        Some(scratch_dir) => scratch_dir.path().to_string_lossy().into_owned(),
+        None => args.secondary_dir.clone().unwrap_or_default(),
+    };
+    let db = timed_open(&args.db_dir, || {
+        open_rocksdb_as_secondary(&args.db_dir, &secondary_dir, None, None)
+    })?;
+
+    let mut last = count_keys(&db, args.exact)?;
+    println!("Keys: {}", last);
+    let mut iteration = 0;
+    while args
+        .iterations
+        .is_none_or(|iterations| iteration < iterations)
+    {
+        std::thread::sleep(Duration::from_secs(args.interval_secs));
+        catch_up(&db)?;
+        let count = count_keys(&db, args.exact)?;
+        println!("Keys: {} ({:+})", count, count as i64 - last as i64);
+        last = count;
+        iteration += 1;
+    }
+
+    // close the DB before its secondary dir is removed
+    drop(db);
+    drop(scratch_dir);
+    Ok(())
+}
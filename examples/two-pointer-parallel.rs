@@ -3,125 +3,134 @@
 //! Usage:
 //! ```
 //! cargo run --example two-pointer-parallel -- --db-dir-left data1.rocksdb --db-dir-right data2.rocksdb
+//! cargo run --example two-pointer-parallel -- --db-dir-left data1.rocksdb --db-dir-right data2.rocksdb --snapshot
+//! cargo run --example two-pointer-parallel -- --db-dir-left data1.rocksdb --db-dir-right data2.rocksdb --num-ranges 64
 //! ```
 //!
 //! This will scan the two DBs for all keys in each DB.
-//! Parallelized by rayon's default thread pool (RAYON_NUM_THREADS); each thread scans the DB for keys that start with the first 4 characters of the hex string.
+//! Parallelized by rayon's default thread pool (RAYON_NUM_THREADS); the keyspace is split into `--num-ranges`
+//! `[lower, upper)` ranges of roughly equal on-disk size across both DBs (see `split_key_ranges_by_combined_size`),
+//! and each thread merges one range, with both iterators bounded to it by ReadOptions. The ranges cover the whole
+//! keyspace, so every key is counted exactly once, including keys outside the hex prefixes, and skewed data in either
+//! DB doesn't leave one thread with most of the work. The min, mean and max time per range are printed to show how even the split is.
+//! The counts match two-pointer-serial's, which is a useful check on a skewed DB from write-skewed.
 //! Key and value are random raw bytes encoded as hex strings.
 //! It will print the total number of keys in each DB and the number of keys in the intersection.
+//! With `--snapshot`, one snapshot per DB is taken up front and every per-range iterator reads from it,
+//! so all shards see each DB at the same point even if it's being written to (e.g. a writable or secondary open).
+//! The read-only opens used here are already frozen at open time, so for them this is a no-op and the counts match.
 
 use anyhow::Result;
 use clap::Parser;
 use rayon::prelude::*;
-use rocksdb_examples::rocksdb_utils::open_rocksdb_for_read_only;
+use rocksdb_examples::rocksdb_utils::{
+    ReadOnlyOpenOptions, TwoPointerCounts, open_rocksdb_for_read_only,
+    split_key_ranges_by_combined_size, timed_open, two_pointer_counts_in_range,
+};
 use rocksdb_examples::utils::{generate_consecutive_hex_strings, make_progress_bar};
-use rust_rocksdb::{Direction, IteratorMode};
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
 struct Cli {
     #[clap(long)]
     db_dir_left: String,
     #[clap(long)]
     db_dir_right: String,
+    /// Read every shard from one snapshot per DB taken up front
+    #[clap(long)]
+    snapshot: bool,
+    /// Number of ranges to split the keyspace into (defaults to the number of rayon threads)
+    #[clap(long)]
+    num_ranges: Option<usize>,
 }
 
 struct Counts {
-    count_left: usize,
-    count_right: usize,
-    count_intersection: usize,
+    totals: TwoPointerCounts,
+    /// elapsed time of each range
+    range_times: Vec<Duration>,
 }
 
 fn main() -> Result<()> {
     let args = Cli::parse();
-    let db_left = open_rocksdb_for_read_only(&args.db_dir_left, true)?;
-    let db_right = open_rocksdb_for_read_only(&args.db_dir_right, true)?;
+    let db_left = timed_open(&args.db_dir_left, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir_left,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+    let db_right = timed_open(&args.db_dir_right, || {
+        open_rocksdb_for_read_only(
+            &args.db_dir_right,
+            ReadOnlyOpenOptions {
+                fast_open_for_iteration: true,
+                ..Default::default()
+            },
+        )
+    })?;
+
+    let snapshot_left = args.snapshot.then(|| db_left.snapshot());
+    let snapshot_right = args.snapshot.then(|| db_right.snapshot());
+    let snapshots = snapshot_left.as_ref().zip(snapshot_right.as_ref());
 
     let prefixes = generate_consecutive_hex_strings(3);
-    let pb = make_progress_bar(Some(prefixes.len() as u64));
+    let num_ranges = args.num_ranges.unwrap_or(rayon::current_num_threads());
+    if num_ranges == 0 {
+        anyhow::bail!("--num-ranges must be at least 1");
+    }
+    // each range reads both DBs, so balance their bytes together, which splits a prefix hot in either of them
+    let ranges = split_key_ranges_by_combined_size(
+        &[&db_left, &db_right],
+        &prefixes,
+        num_ranges.min(prefixes.len()),
+    );
+    let pb = make_progress_bar(Some(ranges.len() as u64));
 
-    let counts = prefixes
+    let counts = ranges
         .into_par_iter()
-        .map(|prefix_str| {
-            let prefix = prefix_str.as_bytes();
-
-            let mut db_iter_left =
-                db_left.full_iterator(IteratorMode::From(prefix, Direction::Forward));
-            let mut db_iter_right =
-                db_right.full_iterator(IteratorMode::From(prefix, Direction::Forward));
-
-            // two pointers
-            let mut count_left = 0;
-            let mut count_right = 0;
-            let mut count_intersection = 0;
-            let mut item_left = db_iter_left.next();
-            let mut item_right = db_iter_right.next();
-
-            // Don't use take() — keep the item we don't advance for the next comparison.
-            while let (Some(Ok((blob_left, _))), Some(Ok((blob_right, _)))) =
-                (item_left.as_ref(), item_right.as_ref())
-            {
-                if &blob_left[..prefix.len()] != prefix || &blob_right[..prefix.len()] != prefix {
-                    break;
-                }
-
-                if blob_left == blob_right {
-                    count_left += 1;
-                    count_right += 1;
-                    count_intersection += 1;
-                    item_left = db_iter_left.next();
-                    item_right = db_iter_right.next();
-                } else if blob_left < blob_right {
-                    count_left += 1;
-                    item_left = db_iter_left.next();
-                } else {
-                    count_right += 1;
-                    item_right = db_iter_right.next();
-                }
-            }
-
-            while let Some(Ok((blob_left, _))) = item_left.as_ref() {
-                if &blob_left[..prefix.len()] != prefix {
-                    break;
-                }
-                count_left += 1;
-                item_left = db_iter_left.next();
-            }
-
-            while let Some(Ok((blob_right, _))) = item_right.as_ref() {
-                if &blob_right[..prefix.len()] != prefix {
-                    break;
-                }
-                count_right += 1;
-                item_right = db_iter_right.next();
-            }
+        .map(|range| -> Result<Counts> {
+            let start = Instant::now();
+            let totals = two_pointer_counts_in_range(&db_left, &db_right, &range, snapshots)?;
 
             pb.inc(1);
-            Counts {
-                count_left,
-                count_right,
-                count_intersection,
-            }
+            Ok(Counts {
+                totals,
+                range_times: vec![start.elapsed()],
+            })
         })
-        .reduce(
+        .try_reduce(
             || Counts {
-                count_left: 0,
-                count_right: 0,
-                count_intersection: 0,
+                totals: TwoPointerCounts::default(),
+                range_times: vec![],
             },
-            |accs, counts| Counts {
-                count_left: accs.count_left + counts.count_left,
-                count_right: accs.count_right + counts.count_right,
-                count_intersection: accs.count_intersection + counts.count_intersection,
+            |mut accs, counts| {
+                accs.totals += counts.totals;
+                accs.range_times.extend(counts.range_times);
+                Ok(accs)
             },
-        );
+        )?;
 
     pb.finish_with_message("done");
 
-    let count_left_unique = counts.count_left - counts.count_intersection;
-    let count_right_unique = counts.count_right - counts.count_intersection;
+    let range_times = &counts.range_times;
+    let counts = counts.totals;
+    println!(
+        "Time per range over {} ranges: min {:.2?} mean {:.2?} max {:.2?}",
+        range_times.len(),
+        range_times.iter().min().copied().unwrap_or_default(),
+        range_times.iter().sum::<Duration>() / range_times.len().max(1) as u32,
+        range_times.iter().max().copied().unwrap_or_default()
+    );
+
+    counts.check()?;
+    let count_left_unique = counts.left - counts.intersection;
+    let count_right_unique = counts.right - counts.intersection;
     println!(
         "Totals:\nleft: {}\nright: {}\nintersection: {}",
-        counts.count_left, counts.count_right, counts.count_intersection
+        counts.left, counts.right, counts.intersection
     );
     println!("Unique:\nleft: {count_left_unique}\nright: {count_right_unique}");
 
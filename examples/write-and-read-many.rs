@@ -0,0 +1,70 @@
+//! Write and read a few entries to RocksDB through the normal durable write path.
+//!
+//! Usage:
+//! ```
+//! cargo run --example write-and-read-many -- --db-dir data.rocksdb --n 1000
+//! cargo run --example write-and-read-many -- --db-dir data.rocksdb --n 1000 --seed 42
+//! ```
+//!
+//! This will write N random entries one put at a time with the WAL on, using `open_rocksdb_for_write`,
+//! then read NUM_READ_BACK of them back and print them.
+//! Key and value are random raw bytes encoded as hex strings.
+//! Unlike write-hex-hashes (parallel, no WAL, compacted), it's single-threaded and leaves the DB as written,
+//! which suits small integration-test fixtures. With `--seed`, the same entries are written on every run.
+
+use anyhow::Result;
+use clap::Parser;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rocksdb_examples::rocksdb_utils::{WriteOpenOptions, open_rocksdb_for_write};
+use rocksdb_examples::utils::{generate_entry, make_progress_bar};
+
+const KEY_LEN: usize = 16;
+const VAL_LEN: usize = 3;
+const NUM_READ_BACK: usize = 5;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Number of entries to write
+    #[arg(long, default_value_t = 1000)]
+    n: usize,
+    /// Seed for the random keys and values (random if omitted)
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    let db = open_rocksdb_for_write(&args.db_dir, WriteOpenOptions::default())?;
+    let mut rng = match args.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => rand::make_rng(),
+    };
+
+    let pb = make_progress_bar(Some(args.n as u64));
+    let mut keys = Vec::with_capacity(NUM_READ_BACK);
+    for i in 0..args.n {
+        let (key, val) = generate_entry(&mut rng, KEY_LEN, VAL_LEN);
+        db.put(key.as_bytes(), val.as_bytes())?;
+        if i < NUM_READ_BACK {
+            keys.push(key);
+        }
+        pb.inc(1);
+    }
+    pb.finish_with_message("done");
+    println!("Wrote {} entries to {}", args.n, args.db_dir);
+
+    for key in &keys {
+        let value = db.get(key.as_bytes())?;
+        println!(
+            "key: {} val: {}",
+            key,
+            value.map_or("key not found".into(), |v| String::from_utf8_lossy(&v)
+                .into_owned())
+        );
+    }
+    Ok(())
+}
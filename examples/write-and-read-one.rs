@@ -3,39 +3,95 @@
 //! Usage:
 //! ```
 //! cargo run --example write_and_read_one -- --db-dir data.rocksdb
+//! cargo run --example write_and_read_one -- --db-dir data.rocksdb --wal-dir /fast/disk/data.wal
+//! cargo run --example write_and_read_one -- --db-dir data.rocksdb --key 00000a2865d3d6f2 --value abc
 //! ```
 //!
 //! This will write a random key and value to the DB and then read the value back.
 //! Key and value are random raw bytes encoded as hex strings.
+//! With `--key` and `--value`, that specific entry is written instead, e.g. to seed a test key; either one
+//! falls back to a random hex string when omitted. It's printed whether the key already existed before the put.
+//! With `--wal-dir`, the WAL lives in a separate directory from the SST files; pass the same one on every run.
+//! The DB is then reopened and the key read again, which recovers the write from that WAL.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
-use rocksdb_examples::rocksdb_utils::open_rocksdb_for_write;
-use rocksdb_examples::utils::generate_random_hex_string;
+use rocksdb_examples::rocksdb_utils::{WriteOpenOptions, open_rocksdb_for_write};
+use rocksdb_examples::utils::{generate_random_hex_string, validate_hex_key};
 
 const KEY_LEN: usize = 16;
 const VAL_LEN: usize = 3;
 
 #[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
 struct Cli {
     #[arg(long)]
     db_dir: String,
+    /// Directory for the WAL, if not the DB directory
+    #[arg(long)]
+    wal_dir: Option<String>,
+    /// Key to write, as lowercase hex (random if omitted)
+    #[arg(long)]
+    key: Option<String>,
+    /// Value to write, as lowercase hex (random if omitted)
+    #[arg(long)]
+    value: Option<String>,
 }
 
 fn main() -> Result<()> {
     let args = Cli::parse();
-    let db = open_rocksdb_for_write(&args.db_dir)?;
+    // validate before opening, so a typo doesn't create a DB
+    if let Some(key) = &args.key {
+        validate_hex_key(key.as_bytes(), None).with_context(|| format!("invalid --key {key:?}"))?;
+    }
+    if let Some(value) = &args.value {
+        validate_hex_key(value.as_bytes(), None)
+            .with_context(|| format!("invalid --value {value:?}"))?;
+    }
+    let db = open_rocksdb_for_write(
+        &args.db_dir,
+        WriteOpenOptions {
+            wal_dir: args.wal_dir.as_deref(),
+            ..Default::default()
+        },
+    )?;
 
-    let key = generate_random_hex_string(KEY_LEN);
-    let val = generate_random_hex_string(VAL_LEN);
+    let key = args
+        .key
+        .clone()
+        .unwrap_or_else(|| generate_random_hex_string(KEY_LEN));
+    let val = args
+        .value
+        .clone()
+        .unwrap_or_else(|| generate_random_hex_string(VAL_LEN));
+    let existed = db.get_pinned(key.as_bytes())?.is_some();
     db.put(key.as_bytes(), val.as_bytes())?;
 
-    println!("key: {}", key);
+    println!("key: {} (existed before: {})", key, existed);
     let value = db.get(key.as_bytes())?;
     if let Some(value) = value {
         println!("val: {}", std::str::from_utf8(&value)?);
     } else {
         println!("key not found");
     }
+
+    if let Some(wal_dir) = &args.wal_dir {
+        // reopen so the unflushed write has to be recovered from the WAL in wal_dir
+        drop(db);
+        let db = open_rocksdb_for_write(
+            &args.db_dir,
+            WriteOpenOptions {
+                wal_dir: Some(wal_dir),
+                ..Default::default()
+            },
+        )?;
+        let value = db.get(key.as_bytes())?;
+        println!(
+            "val after reopen with WAL in {}: {}",
+            wal_dir,
+            value.map_or("key not found".into(), |v| String::from_utf8_lossy(&v)
+                .into_owned())
+        );
+    }
     Ok(())
 }
@@ -3,81 +3,359 @@
 //! Usage:
 //! ```
 //! cargo run --example write-hex-hashes -- --db-dir data.rocksdb
+//! cargo run --example write-hex-hashes -- --db-dir data.rocksdb --manifest data.manifest.json
+//! cargo run --example write-hex-hashes -- --db-dir data.rocksdb --threads-compute 16 --threads-io 2
+//! cargo run --example write-hex-hashes -- --db-dir data.rocksdb --single-writer
 //! ```
 //!
 //! This will write NUM_ENTRIES entries to the DB.
 //! Keys and values are random raw bytes encoded as hex strings.
-//! Parallelized by NUM_THREADS chunks; each thread uses WriteBatch and write without WAL; flush at end. Then compact the DB.
+//! Generator threads (`--threads-compute`, CPU-bound) fill WriteBatches of BATCH_SIZE entries and hand them
+//! through a bounded channel to writer threads (`--threads-io`, IO-bound), which write them without WAL;
+//! flush at end. Then compact the DB. Both thread counts default to NUM_THREADS.
+//! The channel holds at most CHANNEL_BATCHES_PER_WRITER batches per writer, so fast generators block instead of
+//! piling up memory. The busy rate of each side is reported: the lower one is the bottleneck, worth more threads.
+//! With `--single-writer`, one writer thread from `spawn_batch_writer` drains the channel instead, which is enough
+//! when generation is the bottleneck and keeps writes in a single ordered stream.
+//! With `--sync-every N`, the WAL is kept: writers write with the WAL and sync it every N batches.
+//! A crash loses at most the writes since the last sync, a middle ground between no WAL at all and syncing every write.
+//! `--memtable-limit` caps memtable memory with a write buffer manager; memtables flush early and writes stall
+//! at the cap instead of growing up to max_write_buffer_number memtables.
+//! `--bottommost` controls whether compaction rewrites the bottommost level (default force-optimized).
+//! The space amplification (SST files size over live data size) is printed before and after compaction.
+//! `--target-file-size-multiplier` grows the target file size by that factor per level (default 1, uniform);
+//! the SST file count and sizes per level are printed after compaction to show its effect.
+//! `--max-compaction-bytes` caps the size of a single compaction (default essentially unlimited), trading
+//! more, smaller compactions for less memory and shorter stalls on constrained machines.
+//! `--log-level` filters RocksDB's info LOG by severity (default info), and `--log-dir` writes it to another directory,
+//! e.g. one shared by many DBs, instead of in the DB dir.
+//! `--compaction-deadline-secs` bounds the compaction for a maintenance window: the DB is compacted range by range
+//! and it stops at the first range boundary past the deadline, printing how many ranges it got through. Right after
+//! the bulk load everything is still in L0 and the first range compacts all of it, so the deadline only bounds a
+//! DB whose L0 has been compacted before.
+//! `--open-threads` caps the threads opening SST files when reopening an existing DB (defaults to num_cpus),
+//! e.g. in a container where a burst of one thread per core at open would spike the load.
+//! The wall-clock time of each phase (open, write, flush, compaction) is printed at the end.
+//! `--manifest` writes a JSON record of the run (entries, key/value sizes, compression, levels, final on-disk size),
+//! atomically via a temp file and rename. The compression is read back from the DB's OPTIONS file, so it's what
+//! RocksDB actually used.
 
 use anyhow::Result;
 use clap::Parser;
-use rayon::prelude::*;
-use rocksdb_examples::rocksdb_utils::{open_rocksdb_for_bulk_ingestion, print_rocksdb_stats};
-use rocksdb_examples::utils::{generate_random_hex_string, make_progress_bar};
+use rocksdb_examples::rocksdb_utils::{
+    BottommostCompaction, BulkOpenOptions, FlushOnDrop, InfoLogLevel, compact_with_deadline,
+    latest_options_file, make_write_buffer_manager, open_rocksdb_for_bulk_ingestion,
+    parse_options_file, print_level_file_sizes, print_rocksdb_stats, space_amplification,
+    spawn_batch_writer,
+};
+use rocksdb_examples::utils::{
+    PhaseTimer, generate_entry_into, make_progress_bar, write_file_atomically,
+};
 use rust_rocksdb::WriteBatch;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::sync_channel;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 const NUM_THREADS: usize = 8;
 const NUM_ENTRIES: usize = NUM_THREADS * 100_000;
-const ENTRIES_PER_THREAD: usize = NUM_ENTRIES / NUM_THREADS;
 const KEY_LEN: usize = 16;
 const VAL_LEN: usize = 3;
 const ROCKSDB_NUM_LEVELS: i32 = 7;
+const BATCH_SIZE: usize = 10_000;
+const NUM_BATCHES: usize = NUM_ENTRIES / BATCH_SIZE;
+const CHANNEL_BATCHES_PER_WRITER: usize = 2;
 
 #[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
 struct Cli {
     #[arg(long)]
     db_dir: String,
+    /// Bottommost level compaction; use skip for already-compacted data that only needs a level change
+    #[arg(long, value_enum, default_value_t)]
+    bottommost: BottommostCompaction,
+    /// Write a JSON manifest of the run to this path
+    #[arg(long)]
+    manifest: Option<String>,
+    /// Write with the WAL and sync it every N batches of BATCH_SIZE
+    #[arg(long)]
+    sync_every: Option<usize>,
+    /// Number of threads generating entries
+    #[arg(long, default_value_t = NUM_THREADS)]
+    threads_compute: usize,
+    /// Number of threads writing batches to the DB
+    #[arg(long, default_value_t = NUM_THREADS)]
+    threads_io: usize,
+    /// Cap total memtable memory at this many bytes with a write buffer manager, stalling writes when full
+    #[arg(long)]
+    memtable_limit: Option<usize>,
+    /// Target file size growth per level, so higher levels get larger files (default 1)
+    #[arg(long)]
+    target_file_size_multiplier: Option<i32>,
+    /// Cap the input size of a single compaction at this many bytes
+    #[arg(long)]
+    max_compaction_bytes: Option<u64>,
+    /// Minimum severity of messages in RocksDB's info LOG (defaults to info)
+    #[arg(long, value_enum)]
+    log_level: Option<InfoLogLevel>,
+    /// Directory for RocksDB's info LOG instead of the DB dir
+    #[arg(long)]
+    log_dir: Option<String>,
+    /// Stop compacting after this many seconds, at the next range boundary
+    #[arg(long)]
+    compaction_deadline_secs: Option<u64>,
+    /// Write all batches from one `spawn_batch_writer` thread instead of --threads-io writers
+    #[arg(long)]
+    single_writer: bool,
+    /// Number of threads opening SST files (defaults to num_cpus)
+    #[arg(long)]
+    open_threads: Option<i32>,
 }
 
 fn main() -> Result<()> {
     let args = Cli::parse();
-    let db = open_rocksdb_for_bulk_ingestion(&args.db_dir, Some(ROCKSDB_NUM_LEVELS), None)?;
+    if args.sync_every == Some(0) {
+        anyhow::bail!("--sync-every must be at least 1");
+    }
+    if args.single_writer && args.sync_every.is_some() {
+        anyhow::bail!(
+            "--single-writer writes without WAL, so it can't be combined with --sync-every"
+        );
+    }
+    if args.threads_compute == 0 || args.threads_io == 0 {
+        anyhow::bail!("--threads-compute and --threads-io must be at least 1");
+    }
+    let mut timer = PhaseTimer::new();
+    timer.start("open");
+    let write_buffer_manager = args
+        .memtable_limit
+        .map(|bytes| make_write_buffer_manager(bytes, true));
+    let db = open_rocksdb_for_bulk_ingestion(
+        &args.db_dir,
+        BulkOpenOptions {
+            num_levels: Some(ROCKSDB_NUM_LEVELS),
+            write_buffer_manager: write_buffer_manager.as_ref(),
+            file_opening_threads: args.open_threads,
+            target_file_size_multiplier: args.target_file_size_multiplier,
+            max_compaction_bytes: args.max_compaction_bytes,
+            log_level: args.log_level,
+            log_dir: args.log_dir.as_deref(),
+            ..Default::default()
+        },
+    )?;
+    // writes skip the WAL, so flush them even if the run errors out before the flush below
+    let db = FlushOnDrop(&db);
 
     let pb = make_progress_bar(Some(NUM_ENTRIES as u64));
 
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(NUM_THREADS)
-        .build_global()?;
+    let num_batches_generated = AtomicUsize::new(0);
+    let num_batches_written = AtomicUsize::new(0);
+    // time spent generating or writing, excluding time blocked on the channel
+    let compute_busy_nanos = AtomicU64::new(0);
+    let io_busy_nanos = AtomicU64::new(0);
 
-    (0..NUM_THREADS).into_par_iter().for_each(|_| {
-        let mut write_batch = WriteBatch::default();
+    timer.start("write");
+    let start = Instant::now();
+    std::thread::scope(|s| -> Result<()> {
+        let mut handles = vec![];
+        let (sender, single_writer) = if args.single_writer {
+            let (sender, handle) = spawn_batch_writer(s, db.0, CHANNEL_BATCHES_PER_WRITER);
+            (sender, Some(handle))
+        } else {
+            let (sender, receiver) =
+                sync_channel::<WriteBatch>(args.threads_io * CHANNEL_BATCHES_PER_WRITER);
+            // mpsc has a single consumer, so writers take turns receiving; once all writers exit, it's dropped
+            // and blocked generators fail instead of waiting forever
+            let receiver = Arc::new(Mutex::new(receiver));
+            for _ in 0..args.threads_io {
+                let receiver = Arc::clone(&receiver);
+                let (num_batches_written, io_busy_nanos, pb, db, args) =
+                    (&num_batches_written, &io_busy_nanos, &pb, &db, &args);
+                handles.push(s.spawn(move || -> Result<()> {
+                    loop {
+                        let received = receiver.lock().unwrap().recv();
+                        let Ok(write_batch) = received else {
+                            return Ok(());
+                        };
+                        let busy_start = Instant::now();
+                        if let Some(sync_every) = args.sync_every {
+                            db.write(&write_batch)?;
+                            if (num_batches_written.fetch_add(1, Ordering::Relaxed) + 1)
+                                % sync_every
+                                == 0
+                            {
+                                db.flush_wal(true)?;
+                            }
+                        } else {
+                            db.write_without_wal(&write_batch)?;
+                        }
+                        io_busy_nanos
+                            .fetch_add(busy_start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                        pb.inc(write_batch.len() as u64);
+                    }
+                }));
+            }
+            (sender, None)
+        };
 
-        for _ in 0..ENTRIES_PER_THREAD {
-            let key = generate_random_hex_string(KEY_LEN);
-            let val = generate_random_hex_string(VAL_LEN);
-            write_batch.put(key.as_bytes(), val.as_bytes());
-            pb.inc(1);
+        for _ in 0..args.threads_compute {
+            let sender = sender.clone();
+            let (num_batches_generated, compute_busy_nanos, pb, args) =
+                (&num_batches_generated, &compute_busy_nanos, &pb, &args);
+            handles.push(s.spawn(move || -> Result<()> {
+                let mut rng = rand::rng();
+                // reused across entries to avoid two allocations per entry
+                let mut key = String::with_capacity(KEY_LEN);
+                let mut val = String::with_capacity(VAL_LEN);
+                // claim batches until all NUM_BATCHES are taken
+                while num_batches_generated.fetch_add(1, Ordering::Relaxed) < NUM_BATCHES {
+                    let busy_start = Instant::now();
+                    let mut write_batch = WriteBatch::default();
+                    for _ in 0..BATCH_SIZE {
+                        generate_entry_into(&mut rng, KEY_LEN, VAL_LEN, &mut key, &mut val);
+                        write_batch.put(key.as_bytes(), val.as_bytes());
+                    }
+                    compute_busy_nanos
+                        .fetch_add(busy_start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                    sender
+                        .send(write_batch)
+                        .map_err(|_| anyhow::anyhow!("all writers stopped"))?;
+                    // the single writer doesn't report progress, so count batches once handed over
+                    if args.single_writer {
+                        pb.inc(BATCH_SIZE as u64);
+                    }
+                }
+                Ok(())
+            }));
         }
+        // writers stop once every generator is done and has dropped its sender
+        drop(sender);
 
-        db.write_without_wal(&write_batch).unwrap();
-    });
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        if let Some(single_writer) = single_writer {
+            single_writer.join().unwrap()?;
+        }
+        Ok(())
+    })?;
+    let elapsed = start.elapsed();
 
+    timer.start("flush");
     db.flush()?;
+    timer.stop();
 
     pb.finish_with_message("done");
     println!(
         "Wrote {} entries to {} (hex keys and values from random bytes)",
         NUM_ENTRIES, args.db_dir
     );
+    // entries per second of one side if it never waited on the other, i.e. its throughput ceiling
+    let busy_rate = |busy_nanos: &AtomicU64, threads: usize| {
+        let busy_per_thread =
+            Duration::from_nanos(busy_nanos.load(Ordering::Relaxed)) / threads as u32;
+        NUM_ENTRIES as f64 / busy_per_thread.as_secs_f64()
+    };
+    println!(
+        "Overall: {:.0} entries/s in {:.2?}",
+        NUM_ENTRIES as f64 / elapsed.as_secs_f64(),
+        elapsed
+    );
+    println!(
+        "Generate: {:.0} entries/s busy rate with {} threads",
+        busy_rate(&compute_busy_nanos, args.threads_compute),
+        args.threads_compute
+    );
+    if args.single_writer {
+        println!("Write: single writer thread");
+    } else {
+        println!(
+            "Write: {:.0} entries/s busy rate with {} threads",
+            busy_rate(&io_busy_nanos, args.threads_io),
+            args.threads_io
+        );
+    }
+    if let Some(sync_every) = args.sync_every {
+        println!(
+            "Durability window: up to {} batches of {} entries ({} entries) between WAL syncs",
+            sync_every,
+            BATCH_SIZE,
+            sync_every * BATCH_SIZE
+        );
+    }
 
     println!("========================================");
     println!("========== Before compaction: ==========");
     println!("========================================");
     print_rocksdb_stats(&db)?;
+    if let Some(amplification) = space_amplification(&db)? {
+        println!("space-amplification: {:.2}", amplification);
+    }
 
     // Compaction
-    let mut compaction_opts = rust_rocksdb::CompactOptions::default();
-    compaction_opts.set_exclusive_manual_compaction(true);
-    compaction_opts.set_change_level(true);
-    compaction_opts.set_target_level(ROCKSDB_NUM_LEVELS - 1);
-    compaction_opts
-        .set_bottommost_level_compaction(rust_rocksdb::BottommostLevelCompaction::ForceOptimized);
-    db.compact_range_opt(None::<&[u8]>, None::<&[u8]>, &compaction_opts);
+    timer.start("compaction");
+    if let Some(deadline_secs) = args.compaction_deadline_secs {
+        let l0_files = db
+            .property_int_value("rocksdb.num-files-at-level0")?
+            .unwrap_or(0);
+        if l0_files > 0 {
+            eprintln!(
+                "Warning: {} L0 files span the keyspace, so the first range compacts all of them past the deadline",
+                l0_files
+            );
+        }
+        let deadline = Instant::now() + Duration::from_secs(deadline_secs);
+        let (compacted, total) =
+            compact_with_deadline(&db, ROCKSDB_NUM_LEVELS - 1, args.bottommost, deadline);
+        if compacted < total {
+            println!(
+                "Compaction deadline of {}s passed: compacted {} of {} ranges",
+                deadline_secs, compacted, total
+            );
+        }
+    } else {
+        let mut compaction_opts = rust_rocksdb::CompactOptions::default();
+        compaction_opts.set_exclusive_manual_compaction(true);
+        compaction_opts.set_change_level(true);
+        compaction_opts.set_target_level(ROCKSDB_NUM_LEVELS - 1);
+        compaction_opts.set_bottommost_level_compaction(args.bottommost.into());
+        db.compact_range_opt(None::<&[u8]>, None::<&[u8]>, &compaction_opts);
+    }
+    timer.stop();
 
     println!("========================================");
     println!("========== After compaction: ==========");
     println!("========================================");
     print_rocksdb_stats(&db)?;
+    if let Some(amplification) = space_amplification(&db)? {
+        println!("space-amplification: {:.2}", amplification);
+    }
+    print_level_file_sizes(&db)?;
+
+    if let Some(manifest) = &args.manifest {
+        let disk_size = db
+            .property_int_value("rocksdb.live-sst-files-size")?
+            .unwrap_or(0);
+        // the compression RocksDB recorded for this open, rather than what it was asked for
+        let options = parse_options_file(&latest_options_file(&args.db_dir)?)?;
+        let cf_option = |name: &str| options.get(&format!("CFOptions \"default\".{}", name));
+        let fields = serde_json::json!({
+            "db_dir": args.db_dir,
+            "entries": NUM_ENTRIES,
+            "key_len": KEY_LEN,
+            "val_len": VAL_LEN,
+            "compression": cf_option("compression"),
+            "bottommost_compression": cf_option("bottommost_compression"),
+            "num_levels": ROCKSDB_NUM_LEVELS,
+            "disk_size_bytes": disk_size,
+        });
+        write_file_atomically(
+            manifest,
+            &format!("{}\n", serde_json::to_string_pretty(&fields)?),
+        )?;
+        println!("Wrote manifest to {}", manifest);
+    }
 
+    timer.report();
     Ok(())
 }
@@ -0,0 +1,127 @@
+//! Write hex keys with a controllable skew to RocksDB.
+//!
+//! Usage:
+//! ```
+//! cargo run --example write-skewed -- --db-dir skewed.rocksdb
+//! cargo run --example write-skewed -- --db-dir skewed.rocksdb --skew 0.95 --hot-prefix 7f
+//! ```
+//!
+//! This will write NUM_ENTRIES entries with random hex keys of KEY_LEN and values of VAL_LEN, like write-hex-hashes,
+//! except that a `--skew` fraction of the keys (default 0.8) start with `--hot-prefix` (default "00"); the rest are
+//! uniform over the whole keyspace. The hot prefix ends up with far more keys than any other, which uniform keys
+//! can't produce, to exercise the size-balanced ranges and the per-shard timing of parallel-scan.
+//! Entries are written in parallel batches without WAL, flushed and compacted. The number of keys per first hex digit
+//! is counted from the DB and printed at the end.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use rand::RngExt;
+use rayon::prelude::*;
+use rocksdb_examples::rocksdb_utils::{
+    BulkOpenOptions, FlushOnDrop, open_rocksdb_for_bulk_ingestion,
+};
+use rocksdb_examples::utils::{
+    generate_random_hex_string_with_rng, make_progress_bar, validate_hex_key,
+};
+use rust_rocksdb::{IteratorMode, WriteBatch};
+
+const NUM_ENTRIES: usize = 800_000;
+const KEY_LEN: usize = 16;
+const VAL_LEN: usize = 3;
+const ROCKSDB_NUM_LEVELS: i32 = 7;
+const BATCH_SIZE: usize = 10_000;
+
+#[derive(Parser)]
+#[command(version = rocksdb_examples::utils::VERSION_INFO)]
+struct Cli {
+    #[arg(long)]
+    db_dir: String,
+    /// Fraction of keys under --hot-prefix, from 0 (uniform) to 1 (all keys)
+    #[arg(long, default_value_t = 0.8)]
+    skew: f64,
+    /// Prefix of the hot keys, as lowercase hex
+    #[arg(long, default_value = "00")]
+    hot_prefix: String,
+}
+
+fn main() -> Result<()> {
+    let args = Cli::parse();
+    if !(0.0..=1.0).contains(&args.skew) {
+        anyhow::bail!("--skew must be between 0 and 1, got {}", args.skew);
+    }
+    if args.hot_prefix.len() > KEY_LEN {
+        anyhow::bail!("--hot-prefix must be at most {} hex chars", KEY_LEN);
+    }
+    validate_hex_key(args.hot_prefix.as_bytes(), None)
+        .with_context(|| format!("invalid --hot-prefix {:?}", args.hot_prefix))?;
+
+    let db = open_rocksdb_for_bulk_ingestion(
+        &args.db_dir,
+        BulkOpenOptions {
+            num_levels: Some(ROCKSDB_NUM_LEVELS),
+            ..Default::default()
+        },
+    )?;
+    // writes skip the WAL, so flush them even if the run errors out before the flush below
+    let db = FlushOnDrop(&db);
+
+    let pb = make_progress_bar(Some(NUM_ENTRIES as u64));
+    (0..NUM_ENTRIES / BATCH_SIZE)
+        .into_par_iter()
+        .try_for_each(|_| -> Result<()> {
+            let mut rng = rand::rng();
+            let mut write_batch = WriteBatch::default();
+            for _ in 0..BATCH_SIZE {
+                let key = if rng.random_bool(args.skew) {
+                    let suffix = generate_random_hex_string_with_rng(
+                        &mut rng,
+                        KEY_LEN - args.hot_prefix.len(),
+                    );
+                    format!("{}{}", args.hot_prefix, suffix)
+                } else {
+                    generate_random_hex_string_with_rng(&mut rng, KEY_LEN)
+                };
+                let val = generate_random_hex_string_with_rng(&mut rng, VAL_LEN);
+                write_batch.put(key.as_bytes(), val.as_bytes());
+            }
+            db.write_without_wal(&write_batch)?;
+            pb.inc(BATCH_SIZE as u64);
+            Ok(())
+        })?;
+    db.flush()?;
+    pb.finish_with_message("done");
+    println!(
+        "Wrote {} entries to {}, {:.0}% under prefix {}",
+        NUM_ENTRIES,
+        args.db_dir,
+        args.skew * 100.0,
+        args.hot_prefix
+    );
+
+    let mut compaction_opts = rust_rocksdb::CompactOptions::default();
+    compaction_opts.set_exclusive_manual_compaction(true);
+    compaction_opts.set_change_level(true);
+    compaction_opts.set_target_level(ROCKSDB_NUM_LEVELS - 1);
+    compaction_opts
+        .set_bottommost_level_compaction(rust_rocksdb::BottommostLevelCompaction::ForceOptimized);
+    db.compact_range_opt(None::<&[u8]>, None::<&[u8]>, &compaction_opts);
+
+    // keys per first hex digit; random keys can collide, so count what's actually in the DB
+    let mut counts = [0_usize; 16];
+    for item in db.iterator(IteratorMode::Start) {
+        let (key, _value) = item?;
+        let digit = (key[0] as char).to_digit(16).unwrap_or_default();
+        counts[digit as usize] += 1;
+    }
+    let total: usize = counts.iter().sum();
+    println!("Keys per first hex digit:");
+    for (digit, count) in counts.iter().enumerate() {
+        println!(
+            "{:x}: {:>9} ({:5.1}%)",
+            digit,
+            count,
+            *count as f64 * 100.0 / total.max(1) as f64
+        );
+    }
+    Ok(())
+}
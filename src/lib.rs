@@ -1,2 +1,4 @@
+pub mod mapreduce;
+pub mod parscan;
 pub mod rocksdb_utils;
 pub mod utils;
@@ -0,0 +1,142 @@
+//! The map and reduce steps of the map-reduce example, callable on open DBs.
+//!
+//! Map: (key, value) -> (value + sep + hex(key), key), or with `keep_value`, the original key and value encoded
+//! with `encode_composite_key` as the value. Reduce: group the map output by value (everything before the last
+//! `sep`) and join the grouped keys, or with `keep_value`, the `key=value` pairs, with '|'.
+//! Both steps shard their input by its first `prefix_len` hex chars, one rayon task and one WriteBatch per shard,
+//! and write without WAL, so the caller has to flush `output_db`.
+
+use anyhow::{Context, Result};
+use indicatif::ProgressBar;
+use rayon::prelude::*;
+use rust_rocksdb::{DB, Direction, IteratorMode};
+
+use crate::utils::{
+    bytes_to_hex_into, decode_composite_key, encode_composite_key, generate_consecutive_hex_strings,
+};
+
+/// Run the map step from `db` into `output_db`, incrementing `pb` once per shard.
+/// Returns the number of entries mapped.
+pub fn run_map(
+    db: &DB,
+    output_db: &DB,
+    prefix_len: u32,
+    sep: u8,
+    keep_value: bool,
+    pb: &ProgressBar,
+) -> Result<usize> {
+    generate_consecutive_hex_strings(prefix_len)
+        .into_par_iter()
+        .map(|prefix_str| -> Result<usize> {
+            let prefix = prefix_str.as_bytes();
+            let db_iter = db.full_iterator(IteratorMode::From(prefix, Direction::Forward));
+            let mut count = 0;
+            let mut write_batch = rust_rocksdb::WriteBatch::default();
+            let mut key_hex = String::new();
+            for item in db_iter {
+                let (key, value) = item.with_context(|| format!("reading shard {}", prefix_str))?;
+                if !key.starts_with(prefix) {
+                    break;
+                }
+
+                bytes_to_hex_into(&key, &mut key_hex);
+                let new_key: Vec<u8> = value
+                    .iter()
+                    .chain(std::iter::once(&sep))
+                    .chain(key_hex.as_bytes())
+                    .cloned()
+                    .collect();
+                let new_value = if keep_value {
+                    encode_composite_key(&[&key[..], &value[..]])
+                } else {
+                    key.into_vec()
+                };
+
+                write_batch.put(&new_key, &new_value);
+                count += 1;
+            }
+            output_db
+                .write_without_wal(&write_batch)
+                .with_context(|| format!("writing shard {}", prefix_str))?;
+            pb.inc(1);
+            Ok(count)
+        })
+        .try_reduce(|| 0_usize, |acc, c| Ok(acc + c))
+}
+
+/// Run the reduce step from the map output in `db` into `output_db`, incrementing `pb` once per shard.
+/// Returns the number of entries read and of groups written.
+pub fn run_reduce(
+    db: &DB,
+    output_db: &DB,
+    prefix_len: u32,
+    sep: u8,
+    keep_value: bool,
+    pb: &ProgressBar,
+) -> Result<(usize, usize)> {
+    generate_consecutive_hex_strings(prefix_len)
+        .into_par_iter()
+        .map(|prefix_str| -> Result<(usize, usize)> {
+            let prefix = prefix_str.as_bytes();
+            let db_iter = db.full_iterator(IteratorMode::From(prefix, Direction::Forward));
+            let mut count = 0;
+            // a value with the separator inside can sort between the keys of a shorter one, e.g. "ab" + sep + "5"
+            // between "ab" + sep + "3..." and "ab" + sep + "6...", so a group isn't always contiguous
+            let mut groups = std::collections::BTreeMap::<Vec<u8>, Vec<Vec<u8>>>::new();
+            for item in db_iter {
+                let (key, value) =
+                    item.with_context(|| format!("reading shard {}", prefix_str))?;
+                if !key.starts_with(prefix) {
+                    break;
+                }
+
+                // key is value + sep + key_hex; group by value = everything before the last sep,
+                // as key_hex can't contain it
+                let sep_pos = key.iter().rposition(|&b| b == sep).with_context(|| {
+                    format!(
+                        "malformed key {:?} in shard {}: no separator {:?}, was it written by the map step?",
+                        String::from_utf8_lossy(&key),
+                        prefix_str,
+                        sep as char
+                    )
+                })?;
+                let blobs_vec = groups.entry(key[..sep_pos].to_vec()).or_default();
+
+                if keep_value {
+                    let malformed = || {
+                        format!(
+                            "malformed value {:?} of key {:?} in shard {}, was the map step run with --keep-value?",
+                            String::from_utf8_lossy(&value),
+                            String::from_utf8_lossy(&key),
+                            prefix_str
+                        )
+                    };
+                    let parts = decode_composite_key(&value).with_context(malformed)?;
+                    let [original_key, original_value] = parts.as_slice() else {
+                        anyhow::bail!(malformed());
+                    };
+                    blobs_vec.push([&original_key[..], b"=", original_value].concat());
+                } else {
+                    blobs_vec.push(value.to_vec());
+                }
+                count += 1;
+            }
+
+            let mut write_batch = rust_rocksdb::WriteBatch::default();
+            for (new_key, blobs_vec) in &groups {
+                // concatenate with '|'
+                // can use protobuf or anything else to serialize
+                let new_value: Vec<u8> = blobs_vec.join(&b"|"[..]);
+                write_batch.put(new_key, new_value);
+            }
+            output_db
+                .write_without_wal(&write_batch)
+                .with_context(|| format!("writing shard {}", prefix_str))?;
+            pb.inc(1);
+            Ok((count, groups.len()))
+        })
+        .try_reduce(
+            || (0_usize, 0_usize),
+            |accs, counts| Ok((accs.0 + counts.0, accs.1 + counts.1)),
+        )
+}
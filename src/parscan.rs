@@ -0,0 +1,58 @@
+use anyhow::Result;
+use rayon::prelude::*;
+use rust_rocksdb::{DB, DBIterator, IteratorMode, ReadOptions, Snapshot};
+
+use crate::rocksdb_utils::{KVBytes, split_key_ranges_by_size};
+use crate::utils::generate_consecutive_hex_strings;
+
+/// Scan the whole DB in parallel as a rayon `ParallelIterator` of `(key, value)` entries.
+///
+/// The keyspace is split into up to `num_shards` ranges of roughly equal on-disk size (see `split_key_ranges_by_size`),
+/// and each range is read by one bounded iterator on a rayon thread. Entries are in key order within a shard,
+/// but shards are interleaved, so the overall order is unspecified.
+/// Composes with rayon's `map`, `filter`, `reduce`, etc.; iterator errors are yielded as items.
+pub fn par_scan(db: &DB, num_shards: usize) -> impl ParallelIterator<Item = Result<KVBytes>> {
+    let prefixes = generate_consecutive_hex_strings(3);
+    let ranges = split_key_ranges_by_size(db, &prefixes, num_shards.clamp(1, prefixes.len()));
+
+    ranges.into_par_iter().flat_map_iter(move |(lower, upper)| {
+        range_iterator(db, lower, upper).map(|item| item.map_err(anyhow::Error::from))
+    })
+}
+
+/// Iterator over the keys in `[lower, upper)`, as produced by `split_key_ranges_by_size`; None is unbounded.
+pub fn range_iterator(db: &DB, lower: Option<Vec<u8>>, upper: Option<Vec<u8>>) -> DBIterator<'_> {
+    db.iterator_opt(IteratorMode::Start, range_readopts(lower, upper, None))
+}
+
+/// Same as `range_iterator`, but in descending key order, starting from the last key before `upper`.
+/// The bounds keep the seek inside the range, so there's no need to check keys against it.
+pub fn range_iterator_reverse(
+    db: &DB,
+    lower: Option<Vec<u8>>,
+    upper: Option<Vec<u8>>,
+) -> DBIterator<'_> {
+    db.iterator_opt(IteratorMode::End, range_readopts(lower, upper, None))
+}
+
+/// ReadOptions bounding an iterator to `[lower, upper)` (None is unbounded) in total order, so a prefix extractor
+/// doesn't cut the range short, and reading from `snapshot` if given.
+/// The ReadOptions don't borrow the snapshot, so it must outlive every iterator created with them.
+pub fn range_readopts(
+    lower: Option<Vec<u8>>,
+    upper: Option<Vec<u8>>,
+    snapshot: Option<&Snapshot>,
+) -> ReadOptions {
+    let mut readopts = ReadOptions::default();
+    readopts.set_total_order_seek(true);
+    if let Some(snapshot) = snapshot {
+        readopts.set_snapshot(snapshot);
+    }
+    if let Some(lower) = lower {
+        readopts.set_iterate_lower_bound(lower);
+    }
+    if let Some(upper) = upper {
+        readopts.set_iterate_upper_bound(upper);
+    }
+    readopts
+}
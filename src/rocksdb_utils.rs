@@ -1,32 +1,377 @@
 use anyhow::Result;
-use rust_rocksdb::{DB, Options};
+use rayon::prelude::*;
+use rust_rocksdb::{
+    DB, DBIterator, IteratorMode, Options, Range, Snapshot, TransactionDB, TransactionDBOptions,
+    WriteBatch, WriteBufferManager,
+};
 
-/// Open a DB for read-only access.
+use crate::parscan::{range_iterator, range_readopts};
+
+/// How manual compaction treats the bottommost level, selectable from the command line.
 ///
-/// If `fast_open_for_iteration` is true, the DB will be opened without loading the index and filter blocks into memory.
-/// It will make opening faster, but random reads will be slow.
-pub fn open_rocksdb_for_read_only(db_dir: &str, fast_open_for_iteration: bool) -> Result<DB> {
+/// - `skip`: don't rewrite the bottommost level. Fastest when the data is already compacted and only needs a level change.
+/// - `if-filter`: rewrite the bottommost level only if a compaction filter is set (RocksDB's default).
+/// - `force`: always rewrite the bottommost level, including files this compaction just wrote.
+/// - `force-optimized`: always rewrite the bottommost level, but skip files this compaction just wrote.
+///   Needed after bulk loading to fully sort and compress the data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BottommostCompaction {
+    Skip,
+    IfFilter,
+    Force,
+    #[default]
+    ForceOptimized,
+}
+
+impl From<BottommostCompaction> for rust_rocksdb::BottommostLevelCompaction {
+    fn from(value: BottommostCompaction) -> Self {
+        match value {
+            BottommostCompaction::Skip => Self::Skip,
+            BottommostCompaction::IfFilter => Self::IfHaveCompactionFilter,
+            BottommostCompaction::Force => Self::Force,
+            BottommostCompaction::ForceOptimized => Self::ForceOptimized,
+        }
+    }
+}
+
+/// Minimum severity of the messages RocksDB writes to its info LOG, selectable from the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum InfoLogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl From<InfoLogLevel> for rust_rocksdb::LogLevel {
+    fn from(value: InfoLogLevel) -> Self {
+        match value {
+            InfoLogLevel::Debug => Self::Debug,
+            InfoLogLevel::Info => Self::Info,
+            InfoLogLevel::Warn => Self::Warn,
+            InfoLogLevel::Error => Self::Error,
+            InfoLogLevel::Fatal => Self::Fatal,
+        }
+    }
+}
+
+/// Compression used for the SST files of every level, selectable from the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl From<Compression> for rust_rocksdb::DBCompressionType {
+    fn from(value: Compression) -> Self {
+        match value {
+            Compression::None => Self::None,
+            Compression::Lz4 => Self::Lz4,
+            Compression::Zstd => Self::Zstd,
+        }
+    }
+}
+
+/// Optional settings of `open_rocksdb_for_read_only`. `ReadOnlyOpenOptions::default()` is a full open that replays
+/// the WAL, so set only the ones that matter, e.g. `ReadOnlyOpenOptions { fast_open_for_iteration: true,
+/// ..Default::default() }` for a scan.
+#[derive(Clone, Copy, Default)]
+pub struct ReadOnlyOpenOptions {
+    /// If `fast_open_for_iteration` is true, the DB will be opened without loading the index and filter blocks into
+    /// memory. They are read lazily into the block cache on first use and can be evicted from it, so opening is fast
+    /// and memory stays bounded, but random reads are slow. By default bloom filters are not used, since a sequential
+    /// scan never needs them.
+    ///
+    /// If false, every SST file's index and filter blocks are loaded when it's opened and stay in memory for the
+    /// lifetime of the DB, with bloom filters used for point lookups. Opening is slower and uses more memory,
+    /// but random reads are fast from the start.
+    pub fast_open_for_iteration: bool,
+    /// If `bloom_filter_on_fast_open` is true, bloom filters are used in fast open mode too, loaded lazily like the
+    /// rest of the filter blocks. This suits iteration-heavy workloads that still do occasional point lookups.
+    /// It has no effect when `fast_open_for_iteration` is false, which always uses them.
+    pub bloom_filter_on_fast_open: bool,
+    /// If `error_if_log_file_exist` is true, refuse to open if there's unrecovered data, i.e. WAL files that were never
+    /// flushed, which usually means a writer crashed. Useful when auditing a DB's durability state.
+    /// If false, the WAL is replayed into memory and the unflushed data is visible to reads.
+    pub error_if_log_file_exist: bool,
+    /// If `file_opening_threads` is provided, it will be used as the number of threads opening SST files.
+    /// Otherwise, the default of num_cpus::get() will be used. Lower it on a shared box to avoid a burst of I/O
+    /// when opening a DB with many files.
+    pub file_opening_threads: Option<i32>,
+    /// If `block_cache_bytes` is provided, blocks are cached in an LRU cache of that size, see `set_block_cache`.
+    /// Otherwise, RocksDB's default 32MB cache will be used.
+    pub block_cache_bytes: Option<usize>,
+}
+
+/// Open a DB for read-only access, adjusted by `options` (see `ReadOnlyOpenOptions`).
+pub fn open_rocksdb_for_read_only(db_dir: &str, options: ReadOnlyOpenOptions) -> Result<DB> {
+    let opts = options_for_read_only(options);
+    Ok(DB::open_for_read_only(
+        &opts,
+        db_dir,
+        options.error_if_log_file_exist,
+    )?)
+}
+
+/// Open the given column families of a DB for read-only access, to read each with `db.cf_handle(name)`.
+///
+/// The default column family is always opened too. The other column families of the DB can be left out.
+/// All column families get the same block-table tuning as `open_rocksdb_for_read_only`, see it for
+/// `fast_open_for_iteration`; bloom filters are used only without it, and the WAL is replayed.
+/// Names that aren't column families of the DB are an error listing the ones that are.
+pub fn open_rocksdb_for_read_only_cfs(
+    db_dir: &str,
+    cf_names: &[&str],
+    fast_open_for_iteration: bool,
+) -> Result<DB> {
+    let opts = options_for_read_only(ReadOnlyOpenOptions {
+        fast_open_for_iteration,
+        ..Default::default()
+    });
+    let existing = DB::list_cf(&opts, db_dir)?;
+    let missing: Vec<&str> = cf_names
+        .iter()
+        .copied()
+        .filter(|name| !existing.iter().any(|cf| cf == name))
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "column families {:?} not found in {}, it has {:?}",
+            missing,
+            db_dir,
+            existing
+        );
+    }
+    // descriptors rather than open_cf_for_read_only, which would open every column family with default options
+    let cfs = cf_names
+        .iter()
+        .map(|&cf_name| rust_rocksdb::ColumnFamilyDescriptor::new(cf_name, opts.clone()));
+    Ok(DB::open_cf_descriptors_read_only(
+        &opts, db_dir, cfs, false,
+    )?)
+}
+
+/// Open a DB for read-only access with a fixed `prefix_len`-byte prefix extractor, for `db.prefix_iterator`.
+///
+/// With the extractor set, `prefix_iterator` stops by itself after the last key with the seek key's prefix, instead
+/// of the caller checking every key and reading one past the boundary. If the DB was also written with the same
+/// extractor, its SST files have prefix blooms and seeks skip files without the prefix entirely; otherwise the
+/// iterator still stops at the boundary, it just can't skip files. The memtable gets a prefix bloom too, for
+/// the data replayed from the WAL. Otherwise opened like `open_rocksdb_for_read_only` in fast open mode,
+/// with filters loaded lazily.
+pub fn open_rocksdb_for_read_only_with_prefix(db_dir: &str, prefix_len: usize) -> Result<DB> {
+    let mut opts = options_for_read_only(ReadOnlyOpenOptions {
+        fast_open_for_iteration: true,
+        bloom_filter_on_fast_open: true,
+        ..Default::default()
+    });
+    opts.set_prefix_extractor(rust_rocksdb::SliceTransform::create_fixed_prefix(
+        prefix_len,
+    ));
+    opts.set_memtable_prefix_bloom_ratio(0.1);
+    Ok(DB::open_for_read_only(&opts, db_dir, false)?)
+}
+
+/// Options with the block-table tuning of `open_rocksdb_for_read_only`. `error_if_log_file_exist` is an argument
+/// of the open itself, so it's left to the caller.
+fn options_for_read_only(options: ReadOnlyOpenOptions) -> Options {
+    let ReadOnlyOpenOptions {
+        fast_open_for_iteration,
+        bloom_filter_on_fast_open,
+        error_if_log_file_exist: _,
+        file_opening_threads,
+        block_cache_bytes,
+    } = options;
     let mut opts = Options::default();
     let mut table_options = rust_rocksdb::BlockBasedOptions::default();
+    set_block_cache(&mut table_options, block_cache_bytes);
     if fast_open_for_iteration {
+        // load index and filter blocks on demand through the block cache instead of at file open
         table_options.set_cache_index_and_filter_blocks(true);
         // this may blow up memory usage if the DB is uncompacted and full of L0 files, but good for random reads
         // table_options.set_pin_l0_filter_and_index_blocks_in_cache(true);
 
         // this is useful for the TwoLevelIndexSearch index type, good for random reads
         // table_options.set_pin_top_level_index_and_filter(true);
+
+        if bloom_filter_on_fast_open {
+            table_options.set_bloom_filter(10.0, false);
+        }
     } else {
-        // use bloom filter to improve lookup speed
+        // load index and filter blocks at file open and keep them in the table readers, outside the block cache
+        table_options.set_cache_index_and_filter_blocks(false);
+        // use bloom filter to improve lookup speed - the filter blocks in the SST files are only read if a filter policy is set
         table_options.set_bloom_filter(10.0, false);
     }
 
     opts.set_block_based_table_factory(&table_options);
-    opts.set_max_file_opening_threads(num_cpus::get() as i32);
-    Ok(DB::open_for_read_only(&opts, db_dir, false)?)
+    opts.set_max_file_opening_threads(file_opening_threads.unwrap_or(num_cpus::get() as i32));
+    opts
 }
 
-/// Open a DB for regular writing with sane settings.
-pub fn open_rocksdb_for_write(db_dir: &str) -> Result<DB> {
+/// Open a DB as a secondary instance, for reading while other processes, including a writer, have it open.
+///
+/// Read-only opens don't modify the DB or take its LOCK, so several of them can coexist, but each only sees the
+/// files that existed when it opened and can fail once a live writer compacts them away. A secondary instance
+/// follows the primary's MANIFEST instead and can catch up with `try_catch_up_with_primary`.
+/// `secondary_dir` holds the secondary's own info logs and must be unique per instance; it's created if missing.
+/// If `file_opening_threads` or `block_cache_bytes` are provided, they're set as in `open_rocksdb_for_read_only`.
+/// Otherwise, RocksDB's defaults are used.
+pub fn open_rocksdb_as_secondary(
+    db_dir: &str,
+    secondary_dir: &str,
+    file_opening_threads: Option<i32>,
+    block_cache_bytes: Option<usize>,
+) -> Result<DB> {
+    std::fs::create_dir_all(secondary_dir)?;
+    let mut opts = Options::default();
+    // secondaries must keep all files open, or they may lose them to the primary's compactions
+    opts.set_max_open_files(-1);
+    if let Some(file_opening_threads) = file_opening_threads {
+        opts.set_max_file_opening_threads(file_opening_threads);
+    }
+    if block_cache_bytes.is_some() {
+        let mut table_options = rust_rocksdb::BlockBasedOptions::default();
+        set_block_cache(&mut table_options, block_cache_bytes);
+        opts.set_block_based_table_factory(&table_options);
+    }
+    Ok(DB::open_as_secondary(&opts, db_dir, secondary_dir)?)
+}
+
+/// Bring a secondary instance (see `open_rocksdb_as_secondary`) up to date with the primary's flushed and
+/// compacted files and its WAL, so reads see the primary's writes up to now. Call it periodically to tail the DB.
+pub fn catch_up(db: &DB) -> Result<()> {
+    Ok(db.try_catch_up_with_primary()?)
+}
+
+/// Create a checkpoint of `db` in `checkpoint_dir`: a consistent point-in-time copy, taken without stopping writes.
+///
+/// SST files are hard-linked when `checkpoint_dir` is on the same filesystem as the DB, and copied otherwise; the
+/// WAL is flushed or copied so the checkpoint includes every write made before the call. `checkpoint_dir` must not
+/// exist. RocksDB builds the checkpoint in a temp dir next to it and renames it into place at the end, and if
+/// creation fails, anything left at `checkpoint_dir` is removed, so it never holds a half-written checkpoint.
+/// The DB must be open for write, since file deletions are paused while the files are linked.
+pub fn create_checkpoint(db: &DB, checkpoint_dir: &str) -> Result<()> {
+    if std::path::Path::new(checkpoint_dir).exists() {
+        anyhow::bail!("checkpoint dir {} already exists", checkpoint_dir);
+    }
+    let result = rust_rocksdb::checkpoint::Checkpoint::new(db)
+        .and_then(|checkpoint| checkpoint.create_checkpoint(checkpoint_dir));
+    if let Err(e) = result {
+        if std::path::Path::new(checkpoint_dir).exists() {
+            std::fs::remove_dir_all(checkpoint_dir)?;
+        }
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Open a checkpoint (a point-in-time copy made by `create_checkpoint`, see the checkpoint example)
+/// read-only. The checkpoint is a separate directory of hard-linked or copied files, so it keeps showing the DB
+/// as it was when the checkpoint was taken, whatever is written to or compacted in the live DB afterwards.
+pub fn open_rocksdb_for_read_only_at_checkpoint(checkpoint_dir: &str) -> Result<DB> {
+    if !std::path::Path::new(checkpoint_dir)
+        .join("CURRENT")
+        .exists()
+    {
+        anyhow::bail!("{} is not a checkpoint: no CURRENT file", checkpoint_dir);
+    }
+    open_rocksdb_for_read_only(
+        checkpoint_dir,
+        ReadOnlyOpenOptions {
+            fast_open_for_iteration: true,
+            ..Default::default()
+        },
+    )
+}
+
+/// Flushes the wrapped DB's memtables when dropped, including on early returns and panics.
+///
+/// Writes without the WAL (`write_without_wal`, as in the bulk examples) only live in the memtables until a flush,
+/// so an example that errors out between writing and its final flush would otherwise lose them on drop.
+/// Derefs to the DB, so it can be used in its place. A flush error on drop can't be returned, so it's printed.
+pub struct FlushOnDrop<'a>(pub &'a DB);
+
+impl std::ops::Deref for FlushOnDrop<'_> {
+    type Target = DB;
+
+    fn deref(&self) -> &DB {
+        self.0
+    }
+}
+
+impl Drop for FlushOnDrop<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.0.flush() {
+            eprintln!("Error: flush on drop failed: {}", e);
+        }
+    }
+}
+
+/// Spawn a thread in `scope` that writes the WriteBatches sent to it to `db` without WAL, in the order received.
+///
+/// Producers send batches through the returned sender, which can be cloned for each of them; the channel holds at
+/// most `capacity` batches, so fast producers block instead of piling up memory. Once every sender is dropped, the
+/// writer flushes the DB and its handle returns the number of entries written. On a write error it stops and
+/// returns the error, and further sends fail since the receiver is gone.
+pub fn spawn_batch_writer<'scope, 'env>(
+    scope: &'scope std::thread::Scope<'scope, 'env>,
+    db: &'env DB,
+    capacity: usize,
+) -> (
+    std::sync::mpsc::SyncSender<WriteBatch>,
+    std::thread::ScopedJoinHandle<'scope, Result<usize>>,
+) {
+    let (sender, receiver) = std::sync::mpsc::sync_channel::<WriteBatch>(capacity);
+    let handle = scope.spawn(move || -> Result<usize> {
+        let mut count = 0;
+        for write_batch in receiver {
+            db.write_without_wal(&write_batch)?;
+            count += write_batch.len();
+        }
+        db.flush()?;
+        Ok(count)
+    });
+    (sender, handle)
+}
+
+/// Run an open helper and print how long it took and how many SST files the DB has, prefixed by `label`.
+///
+/// Open time grows with the number of SST files, and much more so with `fast_open_for_iteration=false`,
+/// which reads every file's index and filter blocks up front.
+pub fn timed_open<F: FnOnce() -> Result<DB>>(label: &str, f: F) -> Result<DB> {
+    let start = std::time::Instant::now();
+    let db = f()?;
+    let elapsed = start.elapsed();
+    println!(
+        "{}: opened {} SST files in {:.2?}",
+        label,
+        db.live_files()?.len(),
+        elapsed
+    );
+    Ok(db)
+}
+
+/// Turn an open-for-write error caused by an unwritable DB directory into a clear message,
+/// keeping the RocksDB error as the source. Other errors are passed through unchanged.
+fn explain_open_for_write_error(db_dir: &str, e: rust_rocksdb::Error) -> anyhow::Error {
+    let message = e.as_ref();
+    if e.kind() == rust_rocksdb::ErrorKind::IOError
+        && (message.contains("Read-only file system") || message.contains("Permission denied"))
+    {
+        anyhow::Error::new(e).context(format!(
+            "cannot open for write: {} is not writable (read-only filesystem or permission denied)",
+            db_dir
+        ))
+    } else {
+        e.into()
+    }
+}
+
+/// Options for regular writing with sane settings, shared by the write helpers.
+fn options_for_write(block_cache_bytes: Option<usize>) -> Options {
     let mut opts = Options::default();
     opts.create_if_missing(true);
     opts.set_unordered_write(true);
@@ -40,6 +385,7 @@ pub fn open_rocksdb_for_write(db_dir: &str) -> Result<DB> {
 
     // 8KB block size instead of the default 4KB to strike a good balance between memory usage and lookup speed
     table_options.set_block_size(8 * 1024);
+    set_block_cache(&mut table_options, block_cache_bytes);
 
     /*
     // use two-level index search to reduce memory usage by a lot
@@ -54,26 +400,368 @@ pub fn open_rocksdb_for_write(db_dir: &str) -> Result<DB> {
     opts.set_block_based_table_factory(&table_options);
 
     opts.set_max_file_opening_threads(num_cpus::get() as i32);
-    Ok(DB::open(&opts, db_dir)?)
+    // RocksDB's default of 2 background jobs can't keep up with flushes and compactions of a write burst
+    opts.set_max_background_jobs(num_cpus::get() as i32);
+    opts
+}
+
+/// Make a write buffer manager capping the total memtable memory of all DBs and column families sharing it at `bytes`.
+///
+/// Once the cap is hit, the largest memtables are flushed early, and with `allow_stall` writes also stall until
+/// memory frees up. Without a shared manager, each DB and column family can hold up to
+/// `max_write_buffer_number * write_buffer_size` of memtables on its own (e.g. 24 x 64MB with the bulk ingestion
+/// settings), which adds up quickly with many writers on one box.
+pub fn make_write_buffer_manager(bytes: usize, allow_stall: bool) -> WriteBufferManager {
+    WriteBufferManager::new_write_buffer_manager(bytes, allow_stall)
 }
 
-/// Open a DB for bulk loading and compaction.
+/// Cache data blocks (and, when they're cached, index and filter blocks) in an LRU cache of `block_cache_bytes`.
 ///
-/// If `num_levels` is provided, it will be used as the number of levels.
-/// Otherwise, the default bulk loading setting of 2 will be used.
+/// Without it, RocksDB gives each column family's table factory its own 32MB cache, and nothing bounds the total
+/// when several DBs run on one box. The options hold a reference to the cache, and the DB keeps its options, so
+/// the cache lives as long as the DB. It's a fresh cache per open; column families opened with the same options
+/// share it.
+fn set_block_cache(
+    table_options: &mut rust_rocksdb::BlockBasedOptions,
+    block_cache_bytes: Option<usize>,
+) {
+    if let Some(block_cache_bytes) = block_cache_bytes {
+        table_options.set_block_cache(&rust_rocksdb::Cache::new_lru_cache(block_cache_bytes));
+    }
+}
+
+fn set_info_log(opts: &mut Options, log_level: Option<InfoLogLevel>, log_dir: Option<&str>) {
+    if let Some(log_level) = log_level {
+        opts.set_log_level(log_level.into());
+    }
+    if let Some(log_dir) = log_dir {
+        opts.set_db_log_dir(log_dir);
+    }
+}
+
+/// Optional settings of `open_rocksdb_for_write`. `WriteOpenOptions::default()` leaves them all unset,
+/// so set only the ones that matter, e.g. `WriteOpenOptions { wal_dir: Some(dir), ..Default::default() }`.
+#[derive(Clone, Copy, Default)]
+pub struct WriteOpenOptions<'a> {
+    /// If `wal_dir` is provided, the WAL is kept there instead of in `db_dir`, e.g. on a separate fast device.
+    /// The same `wal_dir` must be passed on every later open, and anything that copies or restores the DB
+    /// must carry the WAL dir along, or unflushed writes are lost.
+    pub wal_dir: Option<&'a str>,
+    /// If `write_buffer_manager` is provided, memtable memory is accounted against it
+    /// (see `make_write_buffer_manager`).
+    pub write_buffer_manager: Option<&'a WriteBufferManager>,
+    /// If `deletion_trigger` is provided as `(window_size, num_deletes)`, any SST file with at least `num_deletes`
+    /// tombstones in some `window_size` consecutive entries is marked for compaction, so delete-heavy data gets its
+    /// space reclaimed by automatic compaction, without a manual one.
+    pub deletion_trigger: Option<(usize, usize)>,
+    /// If `file_opening_threads` is provided, it will be used as the number of threads opening SST files.
+    /// Otherwise, the default of num_cpus::get() will be used. Cap it in a container or on a shared box to avoid
+    /// a thread and I/O burst at open; fewer threads make opening a DB with many SST files slower.
+    pub file_opening_threads: Option<i32>,
+    /// If `target_file_size_multiplier` is provided, each level's target file size is that many times the previous
+    /// level's, starting from the 256MB base, so the bottom levels hold fewer, larger files.
+    /// Otherwise, the default of 1 (the same file size on every level) will be used.
+    pub target_file_size_multiplier: Option<i32>,
+    /// If `log_level` is provided, only messages of that severity and above are written to RocksDB's info LOG.
+    /// Otherwise, the default of info will be used.
+    pub log_level: Option<InfoLogLevel>,
+    /// If `log_dir` is provided, the info LOG is written there instead of in `db_dir`, named after the DB's path
+    /// so several DBs can share one log dir. Otherwise, it stays in `db_dir`.
+    pub log_dir: Option<&'a str>,
+    /// If `max_background_jobs` is provided, it will be used as the max number of concurrent background flushes and
+    /// compactions. Otherwise, the default of num_cpus::get() will be used. More jobs let compaction keep up with
+    /// heavy writes and avoid write stalls, but they compete for CPU and disk with the foreground reads and writes;
+    /// lower it when latency matters more than ingest rate, or on a box shared with other work.
+    pub max_background_jobs: Option<i32>,
+    /// If `unordered_write` is provided, it sets whether concurrent writes may become visible out of order.
+    /// Otherwise, the default of true will be used, which lets each write skip waiting for earlier writes' memtable
+    /// inserts, for more write throughput. A reader may then see a later write before an earlier one that's still
+    /// being inserted, so pass false when reads must observe writes in the order they were made, e.g. a reader
+    /// polling a sequence of keys. Snapshots stay consistent either way. The WAL is still appended in write order,
+    /// so recovery after a crash replays writes in order; only what readers see of in-flight writes is relaxed.
+    pub unordered_write: Option<bool>,
+    /// If `block_cache_bytes` is provided, blocks are cached in an LRU cache of that size, see `set_block_cache`.
+    /// Otherwise, RocksDB's default 32MB cache will be used.
+    pub block_cache_bytes: Option<usize>,
+}
+
+/// Open a DB for regular writing with sane settings, adjusted by `options` (see `WriteOpenOptions`).
+pub fn open_rocksdb_for_write(db_dir: &str, options: WriteOpenOptions) -> Result<DB> {
+    let WriteOpenOptions {
+        wal_dir,
+        write_buffer_manager,
+        deletion_trigger,
+        file_opening_threads,
+        target_file_size_multiplier,
+        log_level,
+        log_dir,
+        max_background_jobs,
+        unordered_write,
+        block_cache_bytes,
+    } = options;
+    let mut opts = options_for_write(block_cache_bytes);
+    set_info_log(&mut opts, log_level, log_dir);
+    if let Some(unordered_write) = unordered_write {
+        opts.set_unordered_write(unordered_write);
+    }
+    if let Some(max_background_jobs) = max_background_jobs {
+        opts.set_max_background_jobs(max_background_jobs);
+    }
+    if let Some(target_file_size_multiplier) = target_file_size_multiplier {
+        opts.set_target_file_size_multiplier(target_file_size_multiplier);
+    }
+    if let Some(file_opening_threads) = file_opening_threads {
+        opts.set_max_file_opening_threads(file_opening_threads);
+    }
+    if let Some(wal_dir) = wal_dir {
+        opts.set_wal_dir(wal_dir);
+    }
+    if let Some(write_buffer_manager) = write_buffer_manager {
+        opts.set_write_buffer_manager(write_buffer_manager);
+    }
+    if let Some((window_size, num_deletes)) = deletion_trigger {
+        // a deletion ratio of 0 disables the ratio-based trigger
+        opts.add_compact_on_deletion_collector_factory(window_size, num_deletes, 0.0);
+    }
+    DB::open(&opts, db_dir).map_err(|e| explain_open_for_write_error(db_dir, e))
+}
+
+/// Open a DB for regular writing with the given column families, creating any that are missing.
 ///
-/// If `max_subcompactions` is provided, it will be used as the max number of subcompactions.
-/// Otherwise, the default number of subcompactions of num_cpus::get() will be used.
-pub fn open_rocksdb_for_bulk_ingestion(
+/// All column families use the same settings as `open_rocksdb_for_write`, including `file_opening_threads`.
+pub fn open_rocksdb_for_write_cf(
     db_dir: &str,
-    num_levels: Option<i32>,
-    max_subcompactions: Option<u32>,
+    cf_names: &[&str],
+    file_opening_threads: Option<i32>,
 ) -> Result<DB> {
+    let mut opts = options_for_write(None);
+    opts.create_missing_column_families(true);
+    if let Some(file_opening_threads) = file_opening_threads {
+        opts.set_max_file_opening_threads(file_opening_threads);
+    }
+    let cfs = cf_names.iter().map(|&cf_name| (cf_name, opts.clone()));
+    DB::open_cf_with_opts(&opts, db_dir, cfs).map_err(|e| explain_open_for_write_error(db_dir, e))
+}
+
+/// Name of the `add_u64` merge operator, recorded in the DB's OPTIONS file.
+pub const ADD_U64_MERGE_OPERATOR: &str = "add";
+
+/// Associative merge of u64 counters stored as 8 little-endian bytes: the existing value (0 if missing) plus every
+/// operand, wrapping on overflow. A value or operand of any other length fails the merge, which RocksDB reports as
+/// a corruption error on the read or compaction that hit it, rather than silently miscounting.
+pub fn add_u64(
+    _key: &[u8],
+    existing: Option<&[u8]>,
+    operands: &rust_rocksdb::MergeOperands,
+) -> Option<Vec<u8>> {
+    let mut total = match existing {
+        Some(existing) => u64::from_le_bytes(existing.try_into().ok()?),
+        None => 0,
+    };
+    for operand in operands {
+        total = total.wrapping_add(u64::from_le_bytes(operand.try_into().ok()?));
+    }
+    Some(total.to_le_bytes().to_vec())
+}
+
+/// Open a DB of u64 counters, updated with `db.merge(key, n.to_le_bytes())` and merged with `add_u64`.
+///
+/// Uses the same settings as `open_rocksdb_for_write`. A merge only appends the operand, with no read of the
+/// current value, so concurrent increments of one key never race; operands are combined on reads and compactions.
+/// Every later open of the DB must set the same merge operator, or reads of unmerged counters fail.
+pub fn open_rocksdb_for_counters(db_dir: &str) -> Result<DB> {
+    let mut opts = options_for_write(None);
+    opts.set_merge_operator_associative(ADD_U64_MERGE_OPERATOR, add_u64);
+    DB::open(&opts, db_dir).map_err(|e| explain_open_for_write_error(db_dir, e))
+}
+
+/// Delete every key in `[start, end)` with a single range tombstone, written in a WriteBatch.
+///
+/// Unlike deleting the keys one by one, this writes one entry however many keys the range holds, without reading
+/// them. Reads skip the covered keys right away; compaction drops them and the tombstone later. Keys sort bytewise,
+/// so e.g. `["a", "b")` covers every key starting with `a`. `start` must sort before `end`.
+pub fn delete_range(db: &DB, start: &[u8], end: &[u8]) -> Result<()> {
+    if start >= end {
+        anyhow::bail!(
+            "empty range: start {:?} is not before end {:?}",
+            String::from_utf8_lossy(start),
+            String::from_utf8_lossy(end)
+        );
+    }
+    let mut write_batch = WriteBatch::default();
+    write_batch.delete_range(start, end);
+    Ok(db.write(&write_batch)?)
+}
+
+/// Put `(cf_name, key, value)` entries into their column families atomically with a single WriteBatch.
+///
+/// Either all entries land or none do. Column families are resolved while building the batch,
+/// so an unknown column family fails before anything is written.
+pub fn put_cf_atomically(db: &DB, entries: &[(&str, &[u8], &[u8])]) -> Result<()> {
+    let mut write_batch = WriteBatch::default();
+    for &(cf_name, key, value) in entries {
+        let cf = db
+            .cf_handle(cf_name)
+            .ok_or(anyhow::anyhow!("column family not found: {}", cf_name))?;
+        write_batch.put_cf(cf, key, value);
+    }
+    db.write(&write_batch)?;
+    Ok(())
+}
+
+/// Open a DB for transactional writing with sane settings.
+///
+/// Same as `open_rocksdb_for_write`, including `file_opening_threads` and `block_cache_bytes`, except for
+/// `unordered_write`, which pessimistic transactions don't support.
+pub fn open_rocksdb_for_transactions(
+    db_dir: &str,
+    file_opening_threads: Option<i32>,
+    block_cache_bytes: Option<usize>,
+) -> Result<TransactionDB> {
+    let mut opts = options_for_write(block_cache_bytes);
+    // TransactionDB rejects unordered writes
+    opts.set_unordered_write(false);
+    if let Some(file_opening_threads) = file_opening_threads {
+        opts.set_max_file_opening_threads(file_opening_threads);
+    }
+    let txn_db_opts = TransactionDBOptions::default();
+    TransactionDB::open(&opts, &txn_db_opts, db_dir)
+        .map_err(|e| explain_open_for_write_error(db_dir, e))
+}
+
+/// Put `value` under `key` only if the key doesn't exist yet. Returns whether it was inserted.
+///
+/// The key is locked with `get_for_update` inside a transaction, so concurrent callers can't all see it as absent
+/// and all insert, as they could with a plain `get` followed by `put`. A caller that times out waiting for the lock
+/// (or gets `Busy`) lost the race to another writer: it rolls back and returns false rather than an error.
+pub fn put_if_absent(db: &TransactionDB, key: &[u8], value: &[u8]) -> Result<bool> {
+    let lost_race = |e: &rust_rocksdb::Error| {
+        matches!(
+            e.kind(),
+            rust_rocksdb::ErrorKind::TimedOut | rust_rocksdb::ErrorKind::Busy
+        )
+    };
+    let txn = db.transaction();
+    match txn.get_for_update(key, true) {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            txn.rollback()?;
+            return Ok(false);
+        }
+        Err(e) if lost_race(&e) => {
+            txn.rollback()?;
+            return Ok(false);
+        }
+        Err(e) => return Err(e.into()),
+    }
+    txn.put(key, value)?;
+    match txn.commit() {
+        Ok(()) => Ok(true),
+        Err(e) if lost_race(&e) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Optional settings of `open_rocksdb_for_bulk_ingestion`. `BulkOpenOptions::default()` leaves them all unset,
+/// so set only the ones that matter, e.g. `BulkOpenOptions { num_levels: Some(7), ..Default::default() }`.
+#[derive(Clone, Copy, Default)]
+pub struct BulkOpenOptions<'a> {
+    /// If `num_levels` is provided, it will be used as the number of levels.
+    /// Otherwise, the default bulk loading setting of 2 will be used.
+    pub num_levels: Option<i32>,
+    /// If `max_subcompactions` is provided, it will be used as the max number of subcompactions.
+    /// Otherwise, the default number of subcompactions of num_cpus::get() will be used.
+    pub max_subcompactions: Option<u32>,
+    /// If `write_buffer_manager` is provided, memtable memory is accounted against it
+    /// (see `make_write_buffer_manager`).
+    pub write_buffer_manager: Option<&'a WriteBufferManager>,
+    /// If `file_opening_threads` is provided, it will be used as the number of threads opening SST files.
+    /// Otherwise, the default of num_cpus::get() will be used. Cap it in a container or on a shared box to avoid
+    /// a thread and I/O burst at open; fewer threads make opening a DB with many SST files slower.
+    pub file_opening_threads: Option<i32>,
+    /// If `target_file_size_multiplier` is provided, each level's target file size is that many times the previous
+    /// level's, starting from the 256MB base, so the bottom levels hold fewer, larger files.
+    /// Otherwise, the default of 1 (the same file size on every level) will be used.
+    pub target_file_size_multiplier: Option<i32>,
+    /// If `max_compaction_bytes` is provided, it caps the input size of a single compaction.
+    /// Otherwise, the default of 1PB (essentially unlimited) will be used, so the final compaction can rewrite the
+    /// whole bottommost level in one go for throughput. Smaller caps split it into more, smaller compactions, which
+    /// bounds each one's memory and stall time on constrained machines, at the cost of more intermediate space churn.
+    pub max_compaction_bytes: Option<u64>,
+    /// If `ephemeral` is true, the DB is treated as disposable (e.g. an intermediate map-reduce output): WAL writes
+    /// are kept in an in-memory buffer that's only written out on an explicit `flush_wal`, so even writes made with
+    /// the WAL on never touch the log file. RocksDB has no option to drop the WAL entirely, but with this and
+    /// `write_without_wal` nothing reaches it. Everything not yet flushed to SST files is lost on a crash or an
+    /// unflushed close, so flush before dropping the DB (see `FlushOnDrop`) and rebuild it from its source on failure.
+    pub ephemeral: bool,
+    /// If `log_level` is provided, only messages of that severity and above are written to RocksDB's info LOG.
+    /// Otherwise, the default of info will be used.
+    pub log_level: Option<InfoLogLevel>,
+    /// If `log_dir` is provided, the info LOG is written there instead of in `db_dir`, named after the DB's path
+    /// so several DBs can share one log dir. Otherwise, it stays in `db_dir`.
+    pub log_dir: Option<&'a str>,
+    /// If `compression` is provided, it will be used on every level, e.g. Zstd for highly compressible output.
+    /// Otherwise, the default of Lz4 with Zstd on the bottommost level will be used.
+    pub compression: Option<Compression>,
+    /// If `unordered_write` is provided, it sets whether concurrent writes may become visible out of order, as in
+    /// `WriteOpenOptions`. Otherwise, the default of true will be used.
+    pub unordered_write: Option<bool>,
+    /// If `block_cache_bytes` is provided, blocks are cached in an LRU cache of that size, see `set_block_cache`.
+    /// Otherwise, RocksDB's default 32MB cache will be used.
+    pub block_cache_bytes: Option<usize>,
+    /// If `zstd_dict_bytes` is provided, the bottommost level's Zstd compression uses a dictionary of up to that
+    /// many bytes, trained per SST file on samples of 100 times that size, and so does every level if `compression`
+    /// is Zstd. Small, repetitive values like hex strings share too little within one block to compress well on
+    /// their own, and a dictionary lets each block reuse what's common across the file. Around 16KB to 64KB is
+    /// typical; training slows down compaction a little. Otherwise, no dictionary will be used.
+    pub zstd_dict_bytes: Option<i32>,
+}
+
+/// Open a DB for bulk loading and compaction, adjusted by `options` (see `BulkOpenOptions`).
+pub fn open_rocksdb_for_bulk_ingestion(db_dir: &str, options: BulkOpenOptions) -> Result<DB> {
+    let BulkOpenOptions {
+        num_levels,
+        max_subcompactions,
+        write_buffer_manager,
+        file_opening_threads,
+        target_file_size_multiplier,
+        max_compaction_bytes,
+        ephemeral,
+        log_level,
+        log_dir,
+        compression,
+        unordered_write,
+        block_cache_bytes,
+        zstd_dict_bytes,
+    } = options;
     let mut opts = Options::default();
     opts.create_if_missing(true);
-    opts.set_unordered_write(true);
-    opts.set_compression_type(rust_rocksdb::DBCompressionType::Lz4);
-    opts.set_bottommost_compression_type(rust_rocksdb::DBCompressionType::Zstd);
+    set_info_log(&mut opts, log_level, log_dir);
+    opts.set_unordered_write(unordered_write.unwrap_or(true));
+    if ephemeral {
+        opts.set_manual_wal_flush(true);
+    }
+    match compression {
+        Some(compression) => {
+            opts.set_compression_type(compression.into());
+            opts.set_bottommost_compression_type(compression.into());
+        }
+        None => {
+            opts.set_compression_type(rust_rocksdb::DBCompressionType::Lz4);
+            opts.set_bottommost_compression_type(rust_rocksdb::DBCompressionType::Zstd);
+        }
+    }
+    if let Some(zstd_dict_bytes) = zstd_dict_bytes {
+        // zstd's recommended ratio of training samples to dictionary size
+        let train_bytes = zstd_dict_bytes.saturating_mul(100);
+        // -14 window bits, level 32767 (kDefaultCompressionLevel) and strategy 0 are RocksDB's defaults
+        opts.set_bottommost_compression_options(-14, 32767, 0, zstd_dict_bytes, true);
+        opts.set_bottommost_zstd_max_train_bytes(train_bytes, true);
+        if compression == Some(Compression::Zstd) {
+            opts.set_compression_options(-14, 32767, 0, zstd_dict_bytes);
+            opts.set_zstd_max_train_bytes(train_bytes);
+        }
+    }
 
     // the wonders of bulk loading - https://github.com/facebook/rocksdb/wiki/RocksDB-FAQ
     // https://github.com/facebook/rocksdb/blob/v10.10.1/options/options.cc#L486
@@ -81,9 +769,14 @@ pub fn open_rocksdb_for_bulk_ingestion(
 
     // need to override prepare_for_bulk_load's values because for existing DBs with non-L0 levels,
     // prepare_for_bulk_load will set num_levels to 1 and db open will fail.
-    num_levels.map(|num_levels| opts.set_num_levels(num_levels));
+    if let Some(num_levels) = num_levels {
+        opts.set_num_levels(num_levels);
+    }
 
     opts.set_max_write_buffer_number(24);
+    if let Some(write_buffer_manager) = write_buffer_manager {
+        opts.set_write_buffer_manager(write_buffer_manager);
+    }
 
     let max_flushes = 24;
     opts.set_max_background_jobs(max_flushes);
@@ -104,11 +797,15 @@ pub fn open_rocksdb_for_bulk_ingestion(
     //********************************************************** */
     // 256MB base file size
     opts.set_target_file_size_base(256 * 1024 * 1024);
+    if let Some(target_file_size_multiplier) = target_file_size_multiplier {
+        opts.set_target_file_size_multiplier(target_file_size_multiplier);
+    }
 
     let mut table_options = rust_rocksdb::BlockBasedOptions::default();
 
     // 8KB block size instead of the default 4KB to strike a good balance between memory usage and lookup speed
     table_options.set_block_size(8 * 1024);
+    set_block_cache(&mut table_options, block_cache_bytes);
 
     /*
     // use two-level index search to reduce memory usage by a lot
@@ -128,38 +825,641 @@ pub fn open_rocksdb_for_bulk_ingestion(
     } else {
         opts.set_max_subcompactions(num_cpus::get() as u32);
     }
-    // essentially unlimited upper bound
-    opts.set_max_compaction_bytes(nbytes::bytes![1; PB]);
+    // essentially unlimited upper bound by default
+    opts.set_max_compaction_bytes(max_compaction_bytes.unwrap_or(nbytes::bytes![1; PB]));
 
-    opts.set_max_file_opening_threads(num_cpus::get() as i32);
-    Ok(DB::open(&opts, db_dir)?)
+    opts.set_max_file_opening_threads(file_opening_threads.unwrap_or(num_cpus::get() as i32));
+    DB::open(&opts, db_dir).map_err(|e| explain_open_for_write_error(db_dir, e))
 }
 
-/// Print RocksDB stats.
-pub fn print_rocksdb_stats(db: &DB) -> Result<()> {
-    db.property_value("rocksdb.stats")?.map(|stats| {
-        println!("stats: {}", stats);
-    });
+/// Delete the DB in `db_dir` and all its files, e.g. the output of a previous bulk run before starting over.
+/// The DB must not be open, in this process or any other.
+pub fn destroy_rocksdb(db_dir: &str) -> Result<()> {
+    Ok(DB::destroy(&Options::default(), db_dir)?)
+}
 
-    db.property_value("rocksdb.block-cache-capacity")?
-        .map(|stats| {
-            println!("block-cache-capacity: {}", stats);
-        });
+/// Replace the DB in `live_dir` with the one in `new_dir`, e.g. a DB rebuilt offline, and remove the old one.
+///
+/// The live DB is renamed to `<live_dir>.old`, then `new_dir` is renamed to `live_dir`, and only then the backup is
+/// deleted. Each rename is atomic within one filesystem, so keep `new_dir` next to `live_dir`; across filesystems
+/// the second rename fails and the swap is rolled back. The swap as a whole isn't atomic: between the two renames
+/// `live_dir` briefly doesn't exist, so readers should retry a failed open. If the second rename fails, the backup
+/// is renamed back, so `live_dir` is left as it was. Once the new DB is in place the swap has succeeded, so failing
+/// to delete the backup only prints a warning, and the backup has to be removed by hand before the next swap.
+/// Both DBs must be closed: a process still holding the old DB open keeps reading its files until it reopens.
+/// If `live_dir` doesn't exist yet, `new_dir` is just moved there.
+pub fn swap_db_dirs(new_dir: &str, live_dir: &str) -> Result<()> {
+    use anyhow::Context;
 
-    db.property_value("rocksdb.block-cache-usage")?
-        .map(|stats| {
-            println!("block-cache-usage: {}", stats);
-        });
+    if !std::path::Path::new(new_dir).is_dir() {
+        anyhow::bail!("new DB {} doesn't exist", new_dir);
+    }
+    let backup_dir = format!("{}.old", live_dir);
+    if std::path::Path::new(&backup_dir).exists() {
+        anyhow::bail!(
+            "backup dir {} already exists, from an interrupted swap? Remove it first",
+            backup_dir
+        );
+    }
+    let has_live = std::path::Path::new(live_dir).exists();
+    if has_live {
+        std::fs::rename(live_dir, &backup_dir)
+            .with_context(|| format!("renaming {} to {}", live_dir, backup_dir))?;
+    }
+    if let Err(e) = std::fs::rename(new_dir, live_dir) {
+        if has_live {
+            std::fs::rename(&backup_dir, live_dir).with_context(|| {
+                format!(
+                    "rolling back: renaming {} to {} after failing to rename {} into place ({}), the live DB is at {}",
+                    backup_dir, live_dir, new_dir, e, backup_dir
+                )
+            })?;
+        }
+        return Err(anyhow::Error::new(e).context(format!("renaming {} to {}", new_dir, live_dir)));
+    }
+    if has_live && let Err(e) = std::fs::remove_dir_all(&backup_dir) {
+        eprintln!("Warning: removing the old DB at {}: {}", backup_dir, e);
+    }
+    Ok(())
+}
 
-    db.property_value("rocksdb.block-cache-pinned-usage")?
-        .map(|stats| {
-            println!("block-cache-pinned-usage: {}", stats);
-        });
+/// A `(key, value)` entry as yielded by a `DBIterator`.
+pub type KVBytes = (Box<[u8]>, Box<[u8]>);
+
+/// Advance `db_iter` after `prev`, returning iterator errors instead of ending early.
+///
+/// Merge walks like the two-pointer examples rely on each iterator yielding strictly increasing keys. RocksDB
+/// guarantees that, but a corrupt DB or a mismatched comparator would silently produce wrong counts, so debug
+/// builds assert it; release builds skip the check.
+pub fn next_in_order(db_iter: &mut DBIterator, prev: &Option<KVBytes>) -> Result<Option<KVBytes>> {
+    let item = db_iter.next().transpose()?;
+    if let (Some((prev_key, _)), Some((key, _))) = (prev, &item) {
+        debug_assert!(
+            prev_key < key,
+            "iterator keys not strictly increasing: {:?} then {:?}",
+            String::from_utf8_lossy(prev_key),
+            String::from_utf8_lossy(key)
+        );
+    }
+    Ok(item)
+}
+
+/// Key counts of a two-pointer merge of two DBs (or key ranges), see `two_pointer_counts`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TwoPointerCounts {
+    pub left: usize,
+    pub right: usize,
+    /// Keys present on both sides, also counted in `left` and `right`
+    pub intersection: usize,
+}
 
-    db.property_value("rocksdb.estimate-table-readers-mem")?
-        .map(|stats| {
-            println!("estimate-table-readers-mem: {}", stats);
+impl TwoPointerCounts {
+    /// Fail if the intersection exceeds either side's total. Every intersecting key is counted on both sides,
+    /// so that can only come from a bug in the scan, e.g. ranges that overlap, and the counts can't be trusted.
+    pub fn check(&self) -> Result<()> {
+        if self.intersection > self.left.min(self.right) {
+            anyhow::bail!(
+                "intersection ({}) exceeds a side's total (left: {}, right: {}), the counts are wrong",
+                self.intersection,
+                self.left,
+                self.right
+            );
+        }
+        Ok(())
+    }
+}
+
+impl std::ops::AddAssign for TwoPointerCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.left += other.left;
+        self.right += other.right;
+        self.intersection += other.intersection;
+    }
+}
+
+/// Walk two iterators over strictly increasing keys in lockstep, counting the keys on each side and on both,
+/// e.g. two DBs' `full_iterator`s or two iterators bounded to the same key range. `on_step` is called once per
+/// key visited, for progress reporting.
+///
+/// An empty side yields nothing, so the other side is counted entirely by its tail loop and the intersection is 0.
+/// Iterator errors are returned instead of ending the walk early as if that side ended there.
+pub fn two_pointer_counts(
+    mut db_iter_left: DBIterator,
+    mut db_iter_right: DBIterator,
+    mut on_step: impl FnMut(),
+) -> Result<TwoPointerCounts> {
+    let mut counts = TwoPointerCounts::default();
+    let mut item_left = db_iter_left.next().transpose()?;
+    let mut item_right = db_iter_right.next().transpose()?;
+
+    // Don't use take() — we must keep the item we don't advance so it's compared again next iteration.
+    while let (Some((blob_left, _)), Some((blob_right, _))) =
+        (item_left.as_ref(), item_right.as_ref())
+    {
+        if blob_left == blob_right {
+            counts.left += 1;
+            counts.right += 1;
+            counts.intersection += 1;
+            item_left = next_in_order(&mut db_iter_left, &item_left)?;
+            item_right = next_in_order(&mut db_iter_right, &item_right)?;
+        } else if blob_left < blob_right {
+            counts.left += 1;
+            item_left = next_in_order(&mut db_iter_left, &item_left)?;
+        } else {
+            counts.right += 1;
+            item_right = next_in_order(&mut db_iter_right, &item_right)?;
+        }
+        on_step();
+    }
+
+    while item_left.is_some() {
+        counts.left += 1;
+        item_left = next_in_order(&mut db_iter_left, &item_left)?;
+        on_step();
+    }
+
+    while item_right.is_some() {
+        counts.right += 1;
+        item_right = next_in_order(&mut db_iter_right, &item_right)?;
+        on_step();
+    }
+    Ok(counts)
+}
+
+/// `two_pointer_counts` of the keys of `left` and `right` in `range`, reading from `snapshots` (of `left` and `right`)
+/// if given, so that all ranges of a parallel merge see each DB at the same point even while it's written to.
+/// Disjoint ranges that cover the keyspace, e.g. from `split_key_ranges_by_combined_size`, sum to the full merge.
+pub fn two_pointer_counts_in_range(
+    left: &DB,
+    right: &DB,
+    range: &KeyRange,
+    snapshots: Option<(&Snapshot, &Snapshot)>,
+) -> Result<TwoPointerCounts> {
+    let (lower, upper) = range;
+    let (snapshot_left, snapshot_right) = snapshots.unzip();
+    // the iterators stop at the range's upper bound, so the walk stays within the range
+    two_pointer_counts(
+        left.iterator_opt(
+            IteratorMode::Start,
+            range_readopts(lower.clone(), upper.clone(), snapshot_left),
+        ),
+        right.iterator_opt(
+            IteratorMode::Start,
+            range_readopts(lower.clone(), upper.clone(), snapshot_right),
+        ),
+        || {},
+    )
+}
+
+/// A `[lower, upper)` key range, `None` meaning unbounded on that side.
+pub type KeyRange = (Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Split the keyspace into at most `n` contiguous key ranges of roughly equal on-disk size.
+///
+/// `prefixes` are the sorted candidate split points (e.g. consecutive hex strings); their approximate sizes
+/// are grouped greedily so that each range holds about `1/n` of the bytes. Bounds are `(lower, upper)` with
+/// `None` meaning unbounded, so the ranges together cover the whole keyspace.
+/// If nothing has been flushed to SST files yet, the prefixes are split evenly by count instead.
+pub fn split_key_ranges_by_size(db: &DB, prefixes: &[String], n: usize) -> Vec<KeyRange> {
+    split_key_ranges_by_combined_size(&[db], prefixes, n)
+}
+
+/// Length of the all-0xff key that stands in for "no upper bound" when measuring a range with `get_approximate_sizes`.
+const UNBOUNDED_UPPER_LEN: usize = 1024;
+
+/// Same as `split_key_ranges_by_size`, but balances the bytes of all `dbs` together, for work that reads the same
+/// ranges from each of them, like a merge join. A prefix that's hot in only one DB still gets split.
+pub fn split_key_ranges_by_combined_size(
+    dbs: &[&DB],
+    prefixes: &[String],
+    n: usize,
+) -> Vec<KeyRange> {
+    // the upper bound of a prefix is its `next_prefix`, e.g. "0af" -> "0ag"; only the empty prefix has none, so it's
+    // measured up to a run of 0xff bytes longer than any practical key
+    let uppers: Vec<Vec<u8>> = prefixes
+        .iter()
+        .map(|prefix| {
+            next_prefix(prefix.as_bytes()).unwrap_or_else(|| vec![u8::MAX; UNBOUNDED_UPPER_LEN])
+        })
+        .collect();
+    let ranges: Vec<Range> = prefixes
+        .iter()
+        .zip(uppers.iter())
+        .map(|(prefix, upper)| Range::new(prefix.as_bytes(), upper))
+        .collect();
+    let mut sizes = vec![0_u64; ranges.len()];
+    for db in dbs {
+        for (size, db_size) in sizes.iter_mut().zip(db.get_approximate_sizes(&ranges)) {
+            *size += db_size;
+        }
+    }
+    let total: u64 = sizes.iter().sum();
+
+    let mut split_points: Vec<Vec<u8>> = vec![];
+    if total == 0 {
+        for i in 1..n {
+            split_points.push(prefixes[i * prefixes.len() / n].as_bytes().to_vec());
+        }
+    } else {
+        let target = total as f64 / n as f64;
+        let mut acc = 0_u64;
+        for (i, size) in sizes.iter().enumerate() {
+            if split_points.len() + 1 >= n {
+                break;
+            }
+            if i > 0 && acc as f64 >= target * (split_points.len() + 1) as f64 {
+                split_points.push(prefixes[i].as_bytes().to_vec());
+            }
+            acc += size;
+        }
+    }
+    split_points.dedup();
+    key_ranges_from_split_points(split_points)
+}
+
+/// Turn sorted split points into the contiguous `(lower, upper)` key ranges between them, `None` meaning unbounded,
+/// so the ranges together cover the whole keyspace. `n` split points give `n + 1` ranges.
+pub fn key_ranges_from_split_points(split_points: Vec<Vec<u8>>) -> Vec<KeyRange> {
+    let mut bounds = vec![];
+    let mut lower = None;
+    for split_point in split_points {
+        bounds.push((lower, Some(split_point.clone())));
+        lower = Some(split_point);
+    }
+    bounds.push((lower, None));
+    bounds
+}
+
+/// Path of the sidecar file where `store_split_points` keeps precomputed split points for the DB at `db_dir`:
+/// `<db_dir>.split-points`, next to the DB dir rather than in it, so it stays out of the DB's keyspace
+/// and `DB::destroy` still leaves an empty dir it can remove.
+pub fn split_points_path(db_dir: &str) -> String {
+    format!("{}.split-points", db_dir.trim_end_matches('/'))
+}
+
+/// Store `split_points` (e.g. the uppers of `split_key_ranges_by_size`) in the sidecar file at
+/// `split_points_path(db_dir)`, replacing any stored before, so later jobs can partition the DB with
+/// `load_split_points` without recomputing approximate sizes.
+///
+/// The file has one hex-encoded split point per line and is written atomically. The split points go stale as the DB
+/// changes, so store them again after large writes or compactions.
+pub fn store_split_points(db_dir: &str, split_points: &[Vec<u8>]) -> Result<()> {
+    let mut contents = String::new();
+    for split_point in split_points {
+        contents.push_str(&hex::encode(split_point));
+        contents.push('\n');
+    }
+    crate::utils::write_file_atomically(&split_points_path(db_dir), &contents)
+}
+
+/// Read the split points written by `store_split_points` for the DB at `db_dir`, or None if none were stored.
+pub fn load_split_points(db_dir: &str) -> Result<Option<Vec<Vec<u8>>>> {
+    let path = split_points_path(db_dir);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut split_points = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        let split_point = hex::decode(line).map_err(|e| {
+            anyhow::anyhow!(
+                "malformed split points in {} at line {}: {}",
+                path,
+                i + 1,
+                e
+            )
+        })?;
+        split_points.push(split_point);
+    }
+    Ok(Some(split_points))
+}
+
+/// Number of ranges `compact_with_deadline` splits the keyspace into; more ranges stop closer to the deadline.
+const DEADLINE_COMPACTION_RANGES: usize = 64;
+
+/// Compact the whole DB into `target_level` range by range, stopping once `deadline` has passed.
+///
+/// A running manual compaction can't be canceled through the binding, so the hex keyspace is split into up to
+/// DEADLINE_COMPACTION_RANGES ranges of roughly equal on-disk size (see `split_key_ranges_by_size`), compacted one at a
+/// time, and the clock is checked between ranges. The deadline can be overrun by at most one range's compaction,
+/// but only once L0 is empty: a manual compaction pulls in every L0 file overlapping its range, and after a bulk load
+/// of random keys every L0 file spans the whole keyspace, so the first range rewrites the entire DB whatever the
+/// deadline. The binding can't compact L0 on its own either (`compact_range` carries on down every level), so check
+/// `rocksdb.num-files-at-level0` first, or let automatic compaction drain L0 before relying on the bound.
+/// Ranges are compacted with `bottommost` and left at the bottom; only when all of them are done within the deadline,
+/// the data is moved to `target_level` with a final compaction that only changes the level.
+/// Returns the number of ranges compacted and the total: fewer means it stopped early, and compacted ranges stay
+/// compacted, so a rerun with `BottommostCompaction::Skip` goes quickly over them.
+pub fn compact_with_deadline(
+    db: &DB,
+    target_level: i32,
+    bottommost: BottommostCompaction,
+    deadline: std::time::Instant,
+) -> (usize, usize) {
+    let prefixes = crate::utils::generate_consecutive_hex_strings(3);
+    let ranges = split_key_ranges_by_size(db, &prefixes, DEADLINE_COMPACTION_RANGES);
+    let mut compaction_opts = rust_rocksdb::CompactOptions::default();
+    compaction_opts.set_bottommost_level_compaction(bottommost.into());
+
+    let mut compacted = 0;
+    for (lower, upper) in &ranges {
+        if std::time::Instant::now() >= deadline {
+            return (compacted, ranges.len());
+        }
+        db.compact_range_opt(lower.as_deref(), upper.as_deref(), &compaction_opts);
+        compacted += 1;
+    }
+
+    // all data is compacted, so this only moves the files to the target level
+    let mut compaction_opts = rust_rocksdb::CompactOptions::default();
+    compaction_opts.set_exclusive_manual_compaction(true);
+    compaction_opts.set_change_level(true);
+    compaction_opts.set_target_level(target_level);
+    compaction_opts.set_bottommost_level_compaction(rust_rocksdb::BottommostLevelCompaction::Skip);
+    db.compact_range_opt(None::<&[u8]>, None::<&[u8]>, &compaction_opts);
+    (compacted, ranges.len())
+}
+
+/// How often `compact_range_with_progress` polls the pending compaction bytes.
+const COMPACTION_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Run a manual compaction of `[lower, upper)` (None is unbounded) with `compaction_opts`, showing a progress bar of
+/// RocksDB's estimate of the bytes left to compact, finished once the compaction returns.
+///
+/// The manual compaction blocks, so `rocksdb.estimate-pending-compaction-bytes` is polled from a separate thread.
+/// The bar's length is the largest estimate seen so far and its position how much of it has drained. The estimate
+/// only counts what RocksDB considers overdue by level sizes, so a forced bottommost rewrite can keep running
+/// after the bar is full.
+pub fn compact_range_with_progress(
+    db: &DB,
+    lower: Option<&[u8]>,
+    upper: Option<&[u8]>,
+    compaction_opts: &rust_rocksdb::CompactOptions,
+) {
+    let pb = crate::utils::make_progress_bar_styled(None, crate::utils::ProgressKind::Bar);
+    let done = std::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut max_pending = 0;
+            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                if let Ok(Some(pending)) =
+                    db.property_int_value("rocksdb.estimate-pending-compaction-bytes")
+                {
+                    max_pending = max_pending.max(pending);
+                    pb.set_length(max_pending);
+                    pb.set_position(max_pending - pending);
+                }
+                std::thread::sleep(COMPACTION_PROGRESS_INTERVAL);
+            }
         });
+        db.compact_range_opt(lower, upper, compaction_opts);
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+    });
+    pb.finish_with_message("done");
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// The smallest key that is greater than all keys starting with `prefix`, i.e. an exclusive upper bound for them.
+/// `None` if there is none, for an empty or all-0xff prefix: then all keys from `prefix` on start with it.
+pub fn next_prefix(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut next = prefix.to_vec();
+    while let Some(last) = next.pop() {
+        if last != u8::MAX {
+            next.push(last + 1);
+            return Some(next);
+        }
+    }
+    None
+}
+
+/// Compute a stable fingerprint of the DB contents, or of only the keys starting with `prefix`.
+///
+/// Keys and values are folded in key order into a 64-bit FNV-1a hash, so identical contents always give identical
+/// fingerprints, and any difference in keys or values almost surely changes it.
+/// The keyspace is split into shards by the 3 hex chars after `prefix`, which are hashed in parallel,
+/// and the shard hashes are then folded in key order, so the result doesn't depend on the thread count.
+pub fn db_fingerprint(db: &DB, prefix: Option<&[u8]>) -> Result<u64> {
+    let prefix = prefix.unwrap_or_default();
+    let mut bounds = vec![];
+    let mut lower = (!prefix.is_empty()).then(|| prefix.to_vec());
+    for hex in crate::utils::generate_consecutive_hex_strings(3)
+        .iter()
+        .skip(1)
+    {
+        let split_point = [prefix, hex.as_bytes()].concat();
+        bounds.push((lower, Some(split_point.clone())));
+        lower = Some(split_point);
+    }
+    bounds.push((lower, next_prefix(prefix)));
+
+    let shard_hashes = bounds
+        .into_par_iter()
+        .map(|(lower, upper)| -> Result<u64> {
+            let mut hash = FNV_OFFSET_BASIS;
+            for item in range_iterator(db, lower, upper) {
+                let (key, value) = item?;
+                // length-prefix so ("ab", "c") and ("a", "bc") hash differently
+                hash = fnv1a(hash, &(key.len() as u64).to_le_bytes());
+                hash = fnv1a(hash, &key);
+                hash = fnv1a(hash, &(value.len() as u64).to_le_bytes());
+                hash = fnv1a(hash, &value);
+            }
+            Ok(hash)
+        })
+        .collect::<Result<Vec<u64>>>()?;
+
+    Ok(shard_hashes.iter().fold(FNV_OFFSET_BASIS, |acc, hash| {
+        fnv1a(acc, &hash.to_le_bytes())
+    }))
+}
+
+/// Space amplification: total size of all SST files over the estimated size of live data, so 1.0 means no dead
+/// space and 2.0 means half the files hold overwritten or deleted entries. `None` if the live data size isn't
+/// available or is 0, e.g. on an empty DB.
+pub fn space_amplification(db: &DB) -> Result<Option<f64>> {
+    let live = db.property_int_value("rocksdb.estimate-live-data-size")?;
+    let total = db.property_int_value("rocksdb.total-sst-files-size")?;
+    Ok(match (live, total) {
+        (Some(live), Some(total)) if live > 0 => Some(total as f64 / live as f64),
+        _ => None,
+    })
+}
+
+/// Number of leading keys `detect_db_format` looks at.
+const FORMAT_SAMPLE_SIZE: usize = 16;
+
+/// Key format of a DB, as guessed by `detect_db_format`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DbFormat {
+    /// Lowercase hex strings of one length, as written by write-hex-hashes.
+    Hex { key_len: usize },
+    /// Keys that aren't printable text, best displayed hex-encoded.
+    Binary,
+    /// Empty, or text keys that aren't consistent hex.
+    Unknown,
+}
+
+impl std::fmt::Display for DbFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbFormat::Hex { key_len } => write!(f, "hex keys of length {}", key_len),
+            DbFormat::Binary => write!(f, "binary keys"),
+            DbFormat::Unknown => write!(f, "unknown key format"),
+        }
+    }
+}
+
+/// Guess the key format from the first FORMAT_SAMPLE_SIZE keys, so tools don't have to assume the
+/// write-hex-hashes format. Only the keys are read, so this is cheap even on a large DB.
+pub fn detect_db_format(db: &DB) -> Result<DbFormat> {
+    let mut key_lens = vec![];
+    let mut all_hex = true;
+    for item in db.iterator(IteratorMode::Start).take(FORMAT_SAMPLE_SIZE) {
+        let (key, _) = item?;
+        let is_text = std::str::from_utf8(&key).is_ok_and(|k| !k.chars().any(char::is_control));
+        if !is_text {
+            return Ok(DbFormat::Binary);
+        }
+        all_hex &= key
+            .iter()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(b));
+        key_lens.push(key.len());
+    }
+    Ok(match key_lens.first() {
+        Some(&key_len) if all_hex && key_lens.iter().all(|&len| len == key_len) => {
+            DbFormat::Hex { key_len }
+        }
+        _ => DbFormat::Unknown,
+    })
+}
+
+/// Path of the latest OPTIONS file in `db_dir`, the one RocksDB wrote on the last open or option change.
+///
+/// RocksDB keeps a few OPTIONS-<number> files, numbered in the order they were written, so the highest is current.
+pub fn latest_options_file(db_dir: &str) -> Result<std::path::PathBuf> {
+    let mut latest = None;
+    for entry in std::fs::read_dir(db_dir)? {
+        let path = entry?.path();
+        let number = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("OPTIONS-"))
+            .and_then(|number| number.parse::<u64>().ok());
+        if let Some(number) = number
+            && latest.as_ref().is_none_or(|(latest, _)| number > *latest)
+        {
+            latest = Some((number, path));
+        }
+    }
+    latest
+        .map(|(_, path)| path)
+        .ok_or(anyhow::anyhow!("no OPTIONS file in {}", db_dir))
+}
+
+/// Parse a RocksDB OPTIONS file into `section.name -> value`, e.g. `CFOptions "default".compression -> kLZ4Compression`.
+///
+/// The file is INI-like: `[section]` headers, `name=value` lines, and `#` comments. Section headers are kept as
+/// written, so each column family's options and table options stay apart.
+pub fn parse_options_file(
+    path: &std::path::Path,
+) -> Result<std::collections::BTreeMap<String, String>> {
+    let mut options = std::collections::BTreeMap::new();
+    let mut section = String::new();
+    for line in std::fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = header.to_string();
+        } else if let Some((name, value)) = line.split_once('=') {
+            options.insert(
+                format!("{}.{}", section, name.trim()),
+                value.trim().to_string(),
+            );
+        }
+    }
+    Ok(options)
+}
+
+/// Print the number of SST files per level with their total and average size, from the live files metadata.
+pub fn print_level_file_sizes(db: &DB) -> Result<()> {
+    let mut levels = std::collections::BTreeMap::<i32, (usize, usize)>::new();
+    for f in db.live_files()? {
+        let (count, size) = levels.entry(f.level).or_default();
+        *count += 1;
+        *size += f.size;
+    }
+    for (level, (count, size)) in levels {
+        println!(
+            "L{}: {} files, {} bytes, {} bytes avg",
+            level,
+            count,
+            size,
+            size / count
+        );
+    }
+    Ok(())
+}
+
+/// Memory and size figures of a DB from its integer properties, see `collect_rocksdb_stats`.
+///
+/// Each is None if RocksDB doesn't report the property, e.g. the block cache ones when there's no block cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RocksdbStats {
+    /// `rocksdb.estimate-num-keys`: approximate, overwrites and deletes count until compaction drops them.
+    pub num_keys_estimate: Option<u64>,
+    pub block_cache_capacity: Option<u64>,
+    pub block_cache_usage: Option<u64>,
+    pub block_cache_pinned_usage: Option<u64>,
+    /// `rocksdb.estimate-table-readers-mem`: index and filter blocks held outside the block cache.
+    pub table_readers_mem: Option<u64>,
+    /// `rocksdb.live-sst-files-size`: on-disk size of the SST files of the current version.
+    pub live_sst_size: Option<u64>,
+}
+
+/// Read the `RocksdbStats` of `db`, for checks in code rather than printing.
+pub fn collect_rocksdb_stats(db: &DB) -> Result<RocksdbStats> {
+    Ok(RocksdbStats {
+        num_keys_estimate: db.property_int_value("rocksdb.estimate-num-keys")?,
+        block_cache_capacity: db.property_int_value("rocksdb.block-cache-capacity")?,
+        block_cache_usage: db.property_int_value("rocksdb.block-cache-usage")?,
+        block_cache_pinned_usage: db.property_int_value("rocksdb.block-cache-pinned-usage")?,
+        table_readers_mem: db.property_int_value("rocksdb.estimate-table-readers-mem")?,
+        live_sst_size: db.property_int_value("rocksdb.live-sst-files-size")?,
+    })
+}
+
+/// Print RocksDB stats.
+pub fn print_rocksdb_stats(db: &DB) -> Result<()> {
+    if let Some(stats) = db.property_value("rocksdb.stats")? {
+        println!("stats: {}", stats);
+    }
+
+    let stats = collect_rocksdb_stats(db)?;
+    for (name, value) in [
+        ("estimate-num-keys", stats.num_keys_estimate),
+        ("block-cache-capacity", stats.block_cache_capacity),
+        ("block-cache-usage", stats.block_cache_usage),
+        ("block-cache-pinned-usage", stats.block_cache_pinned_usage),
+        ("estimate-table-readers-mem", stats.table_readers_mem),
+        ("live-sst-files-size", stats.live_sst_size),
+    ] {
+        if let Some(value) = value {
+            println!("{}: {}", name, value);
+        }
+    }
 
     Ok(())
 }
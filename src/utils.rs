@@ -1,43 +1,434 @@
 use indicatif::{ProgressBar, ProgressStyle};
 use rand::RngExt;
 
+/// Crate version with the rust-rocksdb binding and the RocksDB release it builds, printed by every example's
+/// `--version` for bug reports. The binding doesn't expose the linked RocksDB version at runtime, so these are
+/// kept in sync with Cargo.toml by hand (RocksDB's is the `+` suffix of rust-librocksdb-sys's version).
+pub const VERSION_INFO: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (rust-rocksdb 0.46, RocksDB 10.10.1)"
+);
+
 pub fn generate_consecutive_hex_strings(n_digits: u32) -> Vec<String> {
     (0..16_u64.pow(n_digits))
         .map(|i| format!("{i:0width$x}", width = n_digits as usize))
         .collect()
 }
 
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
 pub fn generate_random_hex_string(n_digits: usize) -> String {
-    let mut rng = rand::rng();
-    (0..n_digits)
-        .map(|_| format!("{:x}", rng.random_range(0..16)))
-        .collect()
+    let mut buf = String::with_capacity(n_digits);
+    generate_random_hex_string_into(n_digits, &mut buf);
+    buf
+}
+
+/// Same as `generate_random_hex_string`, but writes into `buf` (cleared first) so hot loops can reuse its allocation.
+pub fn generate_random_hex_string_into(n_digits: usize, buf: &mut String) {
+    generate_random_hex_string_with_rng_into(&mut rand::rng(), n_digits, buf);
+}
+
+/// Same as `generate_random_hex_string`, but draws from `rng`, e.g. a seeded `StdRng` for reproducible data.
+pub fn generate_random_hex_string_with_rng<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    n_digits: usize,
+) -> String {
+    let mut buf = String::with_capacity(n_digits);
+    generate_random_hex_string_with_rng_into(rng, n_digits, &mut buf);
+    buf
+}
+
+/// Lowercase hex encoding of `bytes`, two digits per byte, like `hex::encode`.
+pub fn bytes_to_hex(bytes: &[u8]) -> String {
+    let mut buf = String::with_capacity(bytes.len() * 2);
+    bytes_to_hex_into(bytes, &mut buf);
+    buf
+}
+
+/// Same as `bytes_to_hex`, but writes into `buf` (cleared first) so hot loops can reuse its allocation.
+pub fn bytes_to_hex_into(bytes: &[u8], buf: &mut String) {
+    buf.clear();
+    buf.reserve(bytes.len() * 2);
+    for &b in bytes {
+        buf.push(HEX_DIGITS[(b >> 4) as usize] as char);
+        buf.push(HEX_DIGITS[(b & 0x0f) as usize] as char);
+    }
+}
+
+/// Generate a random hex key and value of the given lengths from `rng`.
+pub fn generate_entry<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    key_len: usize,
+    val_len: usize,
+) -> (String, String) {
+    let mut key = String::with_capacity(key_len);
+    let mut val = String::with_capacity(val_len);
+    generate_entry_into(rng, key_len, val_len, &mut key, &mut val);
+    (key, val)
+}
+
+/// Same as `generate_entry`, but writes into `key` and `val` (cleared first) so hot loops can reuse them.
+pub fn generate_entry_into<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    key_len: usize,
+    val_len: usize,
+    key: &mut String,
+    val: &mut String,
+) {
+    generate_random_hex_string_with_rng_into(rng, key_len, key);
+    generate_random_hex_string_with_rng_into(rng, val_len, val);
+}
+
+fn generate_random_hex_string_with_rng_into<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    n_digits: usize,
+    buf: &mut String,
+) {
+    buf.clear();
+    buf.extend((0..n_digits).map(|_| HEX_DIGITS[rng.random_range(0..16_usize)] as char));
+}
+
+/// Why a key isn't in the crate's hex format, see `validate_hex_key`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HexKeyError {
+    Empty,
+    /// A byte outside 0-9, a-f and A-F.
+    NonHex(u8),
+    /// Valid hex, but uppercase, which sorts apart from the same key in lowercase.
+    Uppercase,
+    WrongLength {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl std::fmt::Display for HexKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HexKeyError::Empty => write!(f, "empty hex string"),
+            HexKeyError::NonHex(b) => write!(f, "unexpected byte {:#04x}, expected 0-9 or a-f", b),
+            HexKeyError::Uppercase => write!(f, "uppercase hex, expected 0-9 or a-f"),
+            HexKeyError::WrongLength { expected, actual } => {
+                write!(f, "length {}, expected {}", actual, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HexKeyError {}
+
+/// Check that `key` is non-empty lowercase hex, like the generated keys, and `expected_len` long if given.
+pub fn validate_hex_key(key: &[u8], expected_len: Option<usize>) -> Result<(), HexKeyError> {
+    if key.is_empty() {
+        return Err(HexKeyError::Empty);
+    }
+    if let Some(&b) = key.iter().find(|b| !b.is_ascii_hexdigit()) {
+        return Err(HexKeyError::NonHex(b));
+    }
+    if key.iter().any(|b| b.is_ascii_uppercase()) {
+        return Err(HexKeyError::Uppercase);
+    }
+    if let Some(expected) = expected_len
+        && key.len() != expected
+    {
+        return Err(HexKeyError::WrongLength {
+            expected,
+            actual: key.len(),
+        });
+    }
+    Ok(())
+}
+
+/// How a progress bar should be drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProgressKind {
+    /// Always draw a bar with ETA, even if the total is unknown.
+    Bar,
+    /// Always draw a spinner, even if the total is known, e.g. when it's only a rough guess and the ETA would mislead.
+    Spinner,
+    /// Draw a bar if the total is known, a spinner otherwise.
+    #[default]
+    Auto,
 }
 
 pub fn make_progress_bar(total: Option<u64>) -> ProgressBar {
+    make_progress_bar_styled(total, ProgressKind::Auto)
+}
+
+pub fn make_progress_bar_styled(total: Option<u64>, kind: ProgressKind) -> ProgressBar {
     let pb;
     let sty;
-    match total {
-        Some(total) => {
-            pb = ProgressBar::new(total);
-            sty = ProgressStyle::with_template(
-                "{spinner:.cyan} [{bar:40.cyan/blue}] {pos:>7}/{len:7} [{elapsed_precise}<{eta_precise} {per_sec:.green}] {msg}"
-            )
-            .unwrap()
-            .progress_chars("█▓▒░");
-        }
-        None => {
-            pb = ProgressBar::new_spinner();
-            sty = ProgressStyle::with_template(
-                "{spinner:.cyan} {pos:>7} [{elapsed_precise} {per_sec:.green}]",
-            )
-            .unwrap();
-        }
+    let as_bar = match kind {
+        ProgressKind::Bar => true,
+        ProgressKind::Spinner => false,
+        ProgressKind::Auto => total.is_some(),
+    };
+    if as_bar {
+        pb = match total {
+            Some(total) => ProgressBar::new(total),
+            None => ProgressBar::no_length(),
+        };
+        sty = ProgressStyle::with_template(
+            "{spinner:.cyan} [{bar:40.cyan/blue}] {pos:>7}/{len:7} [{elapsed_precise}<{eta_precise} {per_sec:.green}] {msg}"
+        )
+        .unwrap()
+        .progress_chars("█▓▒░");
+    } else {
+        pb = ProgressBar::new_spinner();
+        sty = ProgressStyle::with_template(
+            "{spinner:.cyan} {pos:>7} [{elapsed_precise} {per_sec:.green}]",
+        )
+        .unwrap();
     }
     pb.set_style(sty);
     pb
 }
 
+/// Wall-clock time of the consecutive phases of a run (e.g. open, write, flush, compaction), to see where a long
+/// run spends its time. Phases are timed one at a time: starting a phase stops the running one.
+#[derive(Debug, Default)]
+pub struct PhaseTimer {
+    phases: Vec<(String, std::time::Duration)>,
+    running: Option<(String, std::time::Instant)>,
+}
+
+impl PhaseTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start timing the phase `label`, stopping the running one first.
+    pub fn start(&mut self, label: &str) {
+        self.stop();
+        self.running = Some((label.to_string(), std::time::Instant::now()));
+    }
+
+    /// Stop the running phase, if any, and record its time.
+    pub fn stop(&mut self) {
+        if let Some((label, start)) = self.running.take() {
+            self.phases.push((label, start.elapsed()));
+        }
+    }
+
+    /// Stop the running phase and print a table of each phase's time and share of the total.
+    pub fn report(&mut self) {
+        self.stop();
+        let total: std::time::Duration = self.phases.iter().map(|(_, elapsed)| *elapsed).sum();
+        let width = self
+            .phases
+            .iter()
+            .map(|(label, _)| label.len())
+            .max()
+            .unwrap_or(0)
+            .max("total".len());
+        for (label, elapsed) in &self.phases {
+            println!(
+                "{:<width$}  {:>12.2?}  {:>5.1}%",
+                label,
+                elapsed,
+                100.0 * elapsed.as_secs_f64() / total.as_secs_f64().max(f64::MIN_POSITIVE)
+            );
+        }
+        println!("{:<width$}  {:>12.2?}", "total", total);
+    }
+}
+
+/// Write `contents` to `path` atomically: write a temp file next to it, sync it, then rename it into place,
+/// so a crash never leaves a half-written file behind.
+pub fn write_file_atomically(path: &str, contents: &str) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let tmp_path = format!("{path}.tmp");
+    let mut file = std::fs::File::create(&tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// A directory for a run's intermediate files (spilled runs, SST files before ingestion), deleted with everything
+/// in it when dropped, so it's cleaned up whether the run succeeds, errors out or panics.
+///
+/// It's a new `<label>-<pid>` subdirectory of `parent`, or of the system temp dir (`TMPDIR` on Unix) if `parent`
+/// is None, so runs sharing a parent don't collide and the parent itself is never deleted.
+/// Point it at fast local storage when the output lives on a slow or network volume.
+#[derive(Debug)]
+pub struct ScratchDir(std::path::PathBuf);
+
+impl ScratchDir {
+    pub fn create(parent: Option<&str>, label: &str) -> anyhow::Result<Self> {
+        let parent = parent.map_or_else(std::env::temp_dir, std::path::PathBuf::from);
+        let path = parent.join(format!("{}-{}", label, std::process::id()));
+        std::fs::create_dir_all(&parent)?;
+        // create_dir, not create_dir_all: an existing dir could be another run's, which drop would delete
+        std::fs::create_dir(&path)
+            .map_err(|e| anyhow::anyhow!("creating scratch dir {}: {}", path.display(), e))?;
+        Ok(Self(path))
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.0) {
+            eprintln!("Error: removing scratch dir {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+/// Encode a multi-field key so that byte order of the encoded keys matches the order of the field tuples.
+///
+/// Each field is escaped (0x00 becomes 0x00 0xff) and terminated by 0x00 0x01. The terminator sorts before any
+/// escaped or regular byte, so a shorter field sorts before a longer one it is a prefix of, and the encoding of
+/// the leading fields is a prefix of the whole key, usable for prefix scans. Encode numbers as fixed-width
+/// big-endian bytes (e.g. `u64::to_be_bytes`) so they sort numerically.
+pub fn encode_composite_key(parts: &[&[u8]]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(parts.iter().map(|part| part.len() + 2).sum());
+    for part in parts {
+        for &b in *part {
+            key.push(b);
+            if b == 0x00 {
+                key.push(0xff);
+            }
+        }
+        key.extend_from_slice(&[0x00, 0x01]);
+    }
+    key
+}
+
+/// Decode a key written by `encode_composite_key` back into its fields.
+pub fn decode_composite_key(key: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut parts = vec![];
+    let mut part = vec![];
+    let mut bytes = key.iter();
+    while let Some(&b) = bytes.next() {
+        if b != 0x00 {
+            part.push(b);
+            continue;
+        }
+        match bytes.next() {
+            Some(0xff) => part.push(0x00),
+            Some(0x01) => parts.push(std::mem::take(&mut part)),
+            _ => anyhow::bail!("malformed composite key: {:?}", key),
+        }
+    }
+    if !part.is_empty() {
+        anyhow::bail!("malformed composite key: unterminated field in {:?}", key);
+    }
+    Ok(parts)
+}
+
+/// Parse a single-byte separator from the command line: one ASCII character (e.g. `.`) or a `0x`-prefixed byte
+/// (e.g. `0x00`), for bytes that are awkward to type. Meant as a clap `value_parser`.
+pub fn parse_separator(s: &str) -> Result<u8, String> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        return u8::from_str_radix(hex, 16).map_err(|e| format!("invalid byte {s:?}: {e}"));
+    }
+    match s.as_bytes() {
+        [b] if b.is_ascii() => Ok(*b),
+        _ => Err(format!(
+            "expected one ASCII character or a 0x-prefixed byte, got {s:?}"
+        )),
+    }
+}
+
+/// Split `line` at the first `sep` into key and value, so `sep` bytes in the value are kept, or None if there's none.
+pub fn split_first(line: &[u8], sep: u8) -> Option<(&[u8], &[u8])> {
+    let pos = line.iter().position(|&b| b == sep)?;
+    Some((&line[..pos], &line[pos + 1..]))
+}
+
+/// Magic bytes at the start of a binary dump written by export-binary, followed by `DUMP_VERSION`.
+pub const DUMP_MAGIC: &[u8; 8] = b"RDBDUMP\0";
+pub const DUMP_VERSION: u8 = 1;
+
+/// Write the header of a binary dump: `DUMP_MAGIC` then the `DUMP_VERSION` byte.
+pub fn write_dump_header<W: std::io::Write>(w: &mut W) -> std::io::Result<()> {
+    w.write_all(DUMP_MAGIC)?;
+    w.write_all(&[DUMP_VERSION])
+}
+
+/// Read and check the header of a binary dump, failing on a foreign file or an unsupported version.
+pub fn read_dump_header<R: std::io::Read>(r: &mut R) -> anyhow::Result<()> {
+    let mut header = [0; DUMP_MAGIC.len() + 1];
+    r.read_exact(&mut header)
+        .map_err(|e| anyhow::anyhow!("not a binary dump, header too short: {}", e))?;
+    if &header[..DUMP_MAGIC.len()] != DUMP_MAGIC {
+        anyhow::bail!(
+            "not a binary dump, bad magic {:?}",
+            &header[..DUMP_MAGIC.len()]
+        );
+    }
+    let version = header[DUMP_MAGIC.len()];
+    if version != DUMP_VERSION {
+        anyhow::bail!(
+            "unsupported binary dump version {} (expected {})",
+            version,
+            DUMP_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Write one dump record: `[varint key len][key][varint value len][value]`, with LEB128 varints.
+pub fn write_dump_record<W: std::io::Write>(
+    w: &mut W,
+    key: &[u8],
+    value: &[u8],
+) -> std::io::Result<()> {
+    write_varint(w, key.len() as u64)?;
+    w.write_all(key)?;
+    write_varint(w, value.len() as u64)?;
+    w.write_all(value)
+}
+
+/// Read one record written by `write_dump_record`, or None at a clean end of file between records.
+/// A file that ends inside a record is an error, so truncated dumps aren't silently imported.
+pub fn read_dump_record<R: std::io::Read>(r: &mut R) -> anyhow::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let mut first = [0; 1];
+    if r.read(&mut first)? == 0 {
+        return Ok(None);
+    }
+    let key_len = read_varint(r, first[0])?;
+    let mut key = vec![0; usize::try_from(key_len)?];
+    r.read_exact(&mut key)?;
+    let mut first = [0; 1];
+    r.read_exact(&mut first)?;
+    let val_len = read_varint(r, first[0])?;
+    let mut value = vec![0; usize::try_from(val_len)?];
+    r.read_exact(&mut value)?;
+    Ok(Some((key, value)))
+}
+
+fn write_varint<W: std::io::Write>(w: &mut W, mut n: u64) -> std::io::Result<()> {
+    while n >= 0x80 {
+        w.write_all(&[(n as u8) | 0x80])?;
+        n >>= 7;
+    }
+    w.write_all(&[n as u8])
+}
+
+/// Decode a varint whose first byte was already read.
+fn read_varint<R: std::io::Read>(r: &mut R, first: u8) -> anyhow::Result<u64> {
+    let mut n = u64::from(first & 0x7f);
+    let mut byte = first;
+    let mut shift = 7;
+    while byte & 0x80 != 0 {
+        if shift > 63 {
+            anyhow::bail!("malformed varint in binary dump");
+        }
+        let mut buf = [0; 1];
+        r.read_exact(&mut buf)?;
+        byte = buf[0];
+        n |= u64::from(byte & 0x7f) << shift;
+        shift += 7;
+    }
+    Ok(n)
+}
+
 pub fn handle_input() {
     // input
     let mut input = String::new();
@@ -46,3 +437,134 @@ pub fn handle_input() {
         std::process::exit(0);
     }
 }
+
+/// How `display_value` prints a value, e.g. selected with a `--decode` flag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Decode {
+    /// Pretty-printed JSON, falling back to hex for values that don't parse
+    Json,
+    Hex,
+    /// Printable ASCII as is, other bytes as escapes like \x00
+    Escaped,
+}
+
+/// Value as text, or escaped if it isn't valid UTF-8, or decoded as given by `decode`.
+pub fn display_value(value: &[u8], decode: Option<Decode>) -> String {
+    match decode {
+        None => match std::str::from_utf8(value) {
+            Ok(text) => text.to_string(),
+            Err(_) => value.escape_ascii().to_string(),
+        },
+        Some(Decode::Json) => serde_json::from_slice::<serde_json::Value>(value)
+            .ok()
+            .and_then(|json| serde_json::to_string_pretty(&json).ok())
+            .unwrap_or_else(|| format!("(not JSON) {}", hex::encode(value))),
+        Some(Decode::Hex) => hex::encode(value),
+        Some(Decode::Escaped) => value.escape_ascii().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn generate_entry_lengths_and_digits() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for (key_len, val_len) in [(0, 0), (1, 3), (16, 3), (64, 128)] {
+            let (key, val) = generate_entry(&mut rng, key_len, val_len);
+            assert_eq!(key.len(), key_len);
+            assert_eq!(val.len(), val_len);
+            assert!(
+                key.bytes()
+                    .chain(val.bytes())
+                    .all(|b| HEX_DIGITS.contains(&b))
+            );
+        }
+    }
+
+    #[test]
+    fn generate_entry_is_reproducible_with_a_seed() {
+        let entries = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..10)
+                .map(|_| generate_entry(&mut rng, 16, 3))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(entries(42), entries(42));
+        assert_ne!(entries(42), entries(43));
+    }
+
+    #[test]
+    fn generate_entry_into_clears_and_matches_generate_entry() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let expected = generate_entry(&mut rng, 16, 3);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut key = "stale key longer than 16".to_string();
+        let mut val = "stale".to_string();
+        generate_entry_into(&mut rng, 16, 3, &mut key, &mut val);
+        assert_eq!((key, val), expected);
+    }
+
+    #[test]
+    fn bytes_to_hex_into_clears_and_matches_hex_encode() {
+        let mut buf = "stale".to_string();
+        for bytes in [&b""[..], b"\x00", b"ab\x00\n\xff", &[0x0f, 0xf0, 0x9a]] {
+            bytes_to_hex_into(bytes, &mut buf);
+            assert_eq!(buf, hex::encode(bytes));
+            assert_eq!(bytes_to_hex(bytes), buf);
+        }
+    }
+
+    #[test]
+    fn display_value_escapes_binary() {
+        let binary = b"ab\x00\n\xff\"";
+        assert_eq!(display_value(binary, None), r#"ab\x00\n\xff\""#);
+        assert_eq!(
+            display_value(binary, Some(Decode::Escaped)),
+            r#"ab\x00\n\xff\""#
+        );
+        // valid UTF-8 is printed as is without --decode, and escaped byte by byte with it
+        assert_eq!(display_value("tab\té".as_bytes(), None), "tab\té");
+        assert_eq!(
+            display_value("tab\té".as_bytes(), Some(Decode::Escaped)),
+            r"tab\t\xc3\xa9"
+        );
+        assert_eq!(display_value(b"", Some(Decode::Escaped)), "");
+    }
+
+    #[test]
+    fn display_value_decodes() {
+        assert_eq!(display_value(b"\x01\xab", Some(Decode::Hex)), "01ab");
+        assert_eq!(
+            display_value(br#"{"a":1}"#, Some(Decode::Json)),
+            "{\n  \"a\": 1\n}"
+        );
+        assert_eq!(
+            display_value(b"{bad", Some(Decode::Json)),
+            "(not JSON) 7b626164"
+        );
+    }
+
+    #[test]
+    fn split_first_keeps_separators_in_the_value() {
+        fn split(line: &[u8]) -> Option<(&[u8], &[u8])> {
+            split_first(line, b'\t')
+        }
+        assert_eq!(split(b"key\tvalue"), Some((&b"key"[..], &b"value"[..])));
+        assert_eq!(
+            split(b"key\tva\tl\tue"),
+            Some((&b"key"[..], &b"va\tl\tue"[..]))
+        );
+        assert_eq!(split(b"\tvalue"), Some((&b""[..], &b"value"[..])));
+        assert_eq!(split(b"key\t"), Some((&b"key"[..], &b""[..])));
+        assert_eq!(split(b"no separator"), None);
+        assert_eq!(
+            split_first(b"a,b,c", parse_separator(",").unwrap()),
+            Some((&b"a"[..], &b"b,c"[..]))
+        );
+    }
+}
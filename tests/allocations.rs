@@ -0,0 +1,65 @@
+//! Heap allocations of the write path's entry generation, counted by a wrapping global allocator.
+//!
+//! The allocator counts every allocation in this test binary, so it holds a single test.
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rocksdb_examples::utils::{generate_entry, generate_entry_into};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const NUM_ENTRIES: usize = 10_000;
+const KEY_LEN: usize = 32;
+const VAL_LEN: usize = 256;
+
+/// Allocations made by `f`.
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[test]
+fn generate_entry_into_reuses_its_buffers() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let fresh = count_allocations(|| {
+        for _ in 0..NUM_ENTRIES {
+            std::hint::black_box(generate_entry(&mut rng, KEY_LEN, VAL_LEN));
+        }
+    });
+    // a key and a value per entry
+    assert!(fresh >= 2 * NUM_ENTRIES, "{} allocations", fresh);
+
+    let (mut key, mut val) = (String::new(), String::new());
+    let reused = count_allocations(|| {
+        for _ in 0..NUM_ENTRIES {
+            generate_entry_into(&mut rng, KEY_LEN, VAL_LEN, &mut key, &mut val);
+            std::hint::black_box((&key, &val));
+        }
+    });
+    // no per-entry allocations, only the first entry grows the empty buffers
+    assert!(reused < NUM_ENTRIES / 1000, "{} allocations", reused);
+}
@@ -0,0 +1,235 @@
+//! `run_map` and `run_reduce` end to end on small temp DBs, against an in-memory map-reduce of seeded random input.
+
+use anyhow::Result;
+use indicatif::ProgressBar;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use rocksdb_examples::mapreduce::{run_map, run_reduce};
+use rocksdb_examples::rocksdb_utils::{WriteOpenOptions, open_rocksdb_for_write};
+use rocksdb_examples::utils::{ScratchDir, generate_random_hex_string_with_rng};
+use rust_rocksdb::{DB, IteratorMode};
+use std::collections::BTreeMap;
+
+const SEP: u8 = 0x00;
+
+/// Hex keys with hex values, so both the input and the map output (keyed by value) shard by hex prefix.
+const ENTRIES: &[(&str, &str)] = &[("01", "aa"), ("02", "bb"), ("0f", "aa"), ("a0", "aa")];
+
+fn open_db(dir: &ScratchDir, name: &str, entries: &[(&[u8], &[u8])]) -> Result<DB> {
+    let db_dir = dir.path().join(name);
+    let db = open_rocksdb_for_write(&db_dir.to_string_lossy(), WriteOpenOptions::default())?;
+    for (key, value) in entries {
+        db.put(key, value)?;
+    }
+    Ok(db)
+}
+
+fn open_input(dir: &ScratchDir) -> Result<DB> {
+    let entries: Vec<(&[u8], &[u8])> = ENTRIES
+        .iter()
+        .map(|(key, value)| (key.as_bytes(), value.as_bytes()))
+        .collect();
+    open_db(dir, "input", &entries)
+}
+
+fn entries(db: &DB) -> Result<Vec<(String, String)>> {
+    let mut entries = vec![];
+    for item in db.iterator(IteratorMode::Start) {
+        let (key, value) = item?;
+        entries.push((
+            String::from_utf8_lossy(&key).into_owned(),
+            String::from_utf8_lossy(&value).into_owned(),
+        ));
+    }
+    Ok(entries)
+}
+
+#[test]
+fn map_then_reduce_groups_keys_by_value() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-mapreduce-group")?;
+    let input = open_input(&dir)?;
+    let mapped = open_db(&dir, "mapped", &[])?;
+    let reduced = open_db(&dir, "reduced", &[])?;
+    let pb = ProgressBar::hidden();
+
+    let count = run_map(&input, &mapped, 1, SEP, false, &pb)?;
+    assert_eq!(count, ENTRIES.len());
+    let mapped_entries = entries(&mapped)?;
+    assert_eq!(mapped_entries.len(), ENTRIES.len());
+    // value + sep + hex(key) -> key
+    assert_eq!(
+        mapped_entries[0],
+        ("aa\0".to_string() + &hex::encode("01"), "01".to_string())
+    );
+
+    let counts = run_reduce(&mapped, &reduced, 1, SEP, false, &pb)?;
+    assert_eq!(counts, (ENTRIES.len(), 2));
+    assert_eq!(
+        entries(&reduced)?,
+        vec![
+            ("aa".to_string(), "01|0f|a0".to_string()),
+            ("bb".to_string(), "02".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn map_skips_keys_outside_the_hex_shards() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-mapreduce-shards")?;
+    let input = open_db(&dir, "input", &[(b"0a", b"aa"), (b"zz", b"aa")])?;
+    let mapped = open_db(&dir, "mapped", &[])?;
+
+    let count = run_map(&input, &mapped, 1, SEP, false, &ProgressBar::hidden())?;
+    assert_eq!(count, 1);
+    assert_eq!(entries(&mapped)?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn keep_value_round_trips_original_entries() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-mapreduce-keep-value")?;
+    let input = open_input(&dir)?;
+    let mapped = open_db(&dir, "mapped", &[])?;
+    let reduced = open_db(&dir, "reduced", &[])?;
+    let pb = ProgressBar::hidden();
+
+    run_map(&input, &mapped, 1, SEP, true, &pb)?;
+    let counts = run_reduce(&mapped, &reduced, 1, SEP, true, &pb)?;
+    assert_eq!(counts, (ENTRIES.len(), 2));
+    assert_eq!(
+        entries(&reduced)?,
+        vec![
+            ("aa".to_string(), "01=aa|0f=aa|a0=aa".to_string()),
+            ("bb".to_string(), "02=bb".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn reduce_rejects_malformed_map_output() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-mapreduce-malformed")?;
+    let pb = ProgressBar::hidden();
+
+    // a key without the separator wasn't written by the map step
+    let no_sep = open_db(&dir, "no-sep", &[(b"aa", b"01")])?;
+    let reduced = open_db(&dir, "reduced", &[])?;
+    let err = run_reduce(&no_sep, &reduced, 1, SEP, false, &pb).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("malformed key \"aa\" in shard a: no separator"),
+        "{:#}",
+        err
+    );
+
+    // map output without --keep-value doesn't decode as an original key and value
+    let input = open_input(&dir)?;
+    let mapped = open_db(&dir, "mapped", &[])?;
+    run_map(&input, &mapped, 1, SEP, false, &pb)?;
+    let err = run_reduce(&mapped, &reduced, 1, SEP, true, &pb).unwrap_err();
+    assert!(
+        format!("{:#}", err).contains("malformed value"),
+        "{:#}",
+        err
+    );
+    Ok(())
+}
+
+/// Shards of `run_map` and `run_reduce` in `matches_an_in_memory_map_reduce`.
+const PREFIX_LEN: usize = 2;
+
+/// A random value, often colliding with others: short hex values, longer ones sharing the "ab" shard, ones with the
+/// separator inside, and a few that reduce skips because they don't start with PREFIX_LEN hex chars.
+fn random_value(rng: &mut StdRng) -> Vec<u8> {
+    match rng.random_range(0..10) {
+        0..4 => {
+            let len = rng.random_range(2..=3);
+            generate_random_hex_string_with_rng(rng, len).into_bytes()
+        }
+        4..7 => ["ab", "ab0", "abc", "abab"][rng.random_range(0..4)].into(),
+        // the separator inside a value, followed by digits that sort before, between and after hex(key)'s
+        7..9 => {
+            let len = rng.random_range(0..=2);
+            let tail = generate_random_hex_string_with_rng(rng, len);
+            [b"ab", &[SEP][..], tail.as_bytes()].concat()
+        }
+        _ => ["a", "zz", ""][rng.random_range(0..3)].into(),
+    }
+}
+
+/// Straightforward in-memory map-reduce of `input`: keys grouped by value, in key order, joined with '|' (as
+/// `key=value` pairs if `keep_value`), for the values reduce's hex shards cover.
+fn map_reduce_in_memory(
+    input: &BTreeMap<String, Vec<u8>>,
+    keep_value: bool,
+) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    let mut groups = BTreeMap::<Vec<u8>, Vec<Vec<u8>>>::new();
+    for (key, value) in input {
+        let in_shard = value.len() >= PREFIX_LEN
+            && value[..PREFIX_LEN]
+                .iter()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(b));
+        if !in_shard {
+            continue;
+        }
+        let joined = match keep_value {
+            true => [key.as_bytes(), b"=", value].concat(),
+            false => key.as_bytes().to_vec(),
+        };
+        groups.entry(value.clone()).or_default().push(joined);
+    }
+    groups
+        .into_iter()
+        .map(|(value, keys)| (value, keys.join(&b"|"[..])))
+        .collect()
+}
+
+fn raw_entries(db: &DB) -> Result<BTreeMap<Vec<u8>, Vec<u8>>> {
+    let mut entries = BTreeMap::new();
+    for item in db.iterator(IteratorMode::Start) {
+        let (key, value) = item?;
+        entries.insert(key.into_vec(), value.into_vec());
+    }
+    Ok(entries)
+}
+
+#[test]
+fn matches_an_in_memory_map_reduce() -> Result<()> {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut input = BTreeMap::new();
+    for _ in 0..2_000 {
+        let len = rng.random_range(PREFIX_LEN..=6);
+        let key = generate_random_hex_string_with_rng(&mut rng, len);
+        input.insert(key, random_value(&mut rng));
+    }
+
+    for keep_value in [false, true] {
+        let dir = ScratchDir::create(None, &format!("test-mapreduce-random-{}", keep_value))?;
+        let entries: Vec<(&[u8], &[u8])> = input
+            .iter()
+            .map(|(key, value)| (key.as_bytes(), value.as_slice()))
+            .collect();
+        let input_db = open_db(&dir, "input", &entries)?;
+        let mapped = open_db(&dir, "mapped", &[])?;
+        let reduced = open_db(&dir, "reduced", &[])?;
+        let pb = ProgressBar::hidden();
+
+        let expected = map_reduce_in_memory(&input, keep_value);
+        let count = run_map(&input_db, &mapped, PREFIX_LEN as u32, SEP, keep_value, &pb)?;
+        assert_eq!(count, input.len());
+        let (count, count_grouped) =
+            run_reduce(&mapped, &reduced, PREFIX_LEN as u32, SEP, keep_value, &pb)?;
+        let count_expected = expected
+            .values()
+            .map(|keys| keys.split(|&b| b == b'|').count())
+            .sum::<usize>();
+        assert_eq!((count, count_grouped), (count_expected, expected.len()));
+        assert_eq!(
+            raw_entries(&reduced)?,
+            expected,
+            "keep_value {}",
+            keep_value
+        );
+    }
+    Ok(())
+}
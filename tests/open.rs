@@ -0,0 +1,188 @@
+//! The open helpers of `rocksdb_utils` on small temp DBs.
+
+use anyhow::Result;
+use rocksdb_examples::rocksdb_utils::{
+    BulkOpenOptions, Compression, ReadOnlyOpenOptions, WriteOpenOptions, collect_rocksdb_stats,
+    open_rocksdb_for_bulk_ingestion, open_rocksdb_for_read_only, open_rocksdb_for_read_only_cfs,
+    open_rocksdb_for_read_only_with_prefix, open_rocksdb_for_write, open_rocksdb_for_write_cf,
+};
+use rocksdb_examples::utils::ScratchDir;
+use rust_rocksdb::DB;
+
+#[test]
+fn read_only_cfs_opens_the_requested_column_families() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-open-cfs")?;
+    let db_dir = dir.path().join("db").to_string_lossy().into_owned();
+    {
+        let db = open_rocksdb_for_write_cf(&db_dir, &["users", "events"], None)?;
+        for cf_name in ["users", "events"] {
+            let cf = db.cf_handle(cf_name).unwrap();
+            db.put_cf(cf, "key", cf_name)?;
+        }
+    }
+
+    let db = open_rocksdb_for_read_only_cfs(&db_dir, &["users", "events"], false)?;
+    for cf_name in ["users", "events"] {
+        let cf = db.cf_handle(cf_name).unwrap();
+        assert_eq!(db.get_cf(cf, "key")?.as_deref(), Some(cf_name.as_bytes()));
+    }
+    // the default column family is opened too, and holds none of the entries
+    assert_eq!(db.get("key")?, None);
+    drop(db);
+
+    // a subset is fine, an unknown name isn't
+    let db = open_rocksdb_for_read_only_cfs(&db_dir, &["events"], true)?;
+    assert!(db.cf_handle("users").is_none());
+    drop(db);
+    let err = open_rocksdb_for_read_only_cfs(&db_dir, &["users", "missing"], false).unwrap_err();
+    assert!(
+        err.to_string().contains("[\"missing\"] not found"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn block_cache_has_the_requested_capacity() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-open-block-cache")?;
+    let db_dir = dir.path().join("db").to_string_lossy().into_owned();
+    let bytes = 8 << 20;
+    let capacity =
+        |db: &DB| -> Result<Option<u64>> { Ok(collect_rocksdb_stats(db)?.block_cache_capacity) };
+
+    let db = open_rocksdb_for_write(
+        &db_dir,
+        WriteOpenOptions {
+            block_cache_bytes: Some(bytes),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(capacity(&db)?, Some(bytes as u64));
+    drop(db);
+
+    let db = open_rocksdb_for_bulk_ingestion(
+        &db_dir,
+        BulkOpenOptions {
+            block_cache_bytes: Some(2 * bytes),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(capacity(&db)?, Some(2 * bytes as u64));
+    drop(db);
+
+    let db = open_rocksdb_for_read_only(
+        &db_dir,
+        ReadOnlyOpenOptions {
+            block_cache_bytes: Some(3 * bytes),
+            ..Default::default()
+        },
+    )?;
+    assert_eq!(capacity(&db)?, Some(3 * bytes as u64));
+    drop(db);
+
+    // without it, RocksDB's default 32MB cache
+    let db = open_rocksdb_for_read_only(&db_dir, ReadOnlyOpenOptions::default())?;
+    assert_eq!(capacity(&db)?, Some(32 << 20));
+    Ok(())
+}
+
+#[test]
+fn zstd_dictionary_shrinks_short_repetitive_values() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-open-zstd-dict")?;
+    let sst_size = |name: &str, zstd_dict_bytes: Option<i32>| -> Result<u64> {
+        let db_dir = dir.path().join(name).to_string_lossy().into_owned();
+        let db = open_rocksdb_for_bulk_ingestion(
+            &db_dir,
+            BulkOpenOptions {
+                num_levels: Some(7),
+                compression: Some(Compression::Zstd),
+                zstd_dict_bytes,
+                ..Default::default()
+            },
+        )?;
+        for i in 0..50_000_u32 {
+            let value = format!(
+                r#"{{"id":"{:08x}","status":"active","region":"us-east-1","tier":{}}}"#,
+                i.wrapping_mul(2_654_435_761),
+                i % 3
+            );
+            db.put(format!("{:08x}", i), value)?;
+        }
+        db.flush()?;
+        let mut compaction_opts = rust_rocksdb::CompactOptions::default();
+        compaction_opts.set_change_level(true);
+        compaction_opts.set_target_level(6);
+        compaction_opts
+            .set_bottommost_level_compaction(rust_rocksdb::BottommostLevelCompaction::Force);
+        db.compact_range_opt(None::<&[u8]>, None::<&[u8]>, &compaction_opts);
+        Ok(collect_rocksdb_stats(&db)?.live_sst_size.unwrap_or(0))
+    };
+
+    let without = sst_size("without", None)?;
+    let with = sst_size("with", Some(16 * 1024))?;
+    assert!(without > 0);
+    assert!(
+        with < without,
+        "with a dictionary: {} bytes, without: {} bytes",
+        with,
+        without
+    );
+    Ok(())
+}
+
+#[test]
+fn prefix_iterator_stops_at_the_prefix_end() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-open-prefix")?;
+    let db_dir = dir.path().join("db").to_string_lossy().into_owned();
+    {
+        let db = open_rocksdb_for_write(&db_dir, WriteOpenOptions::default())?;
+        for key in ["09f", "0a", "0a0", "0a1", "0aff", "0b", "0b0", "1a0"] {
+            db.put(key, b"")?;
+        }
+        db.flush()?;
+    }
+
+    let db = open_rocksdb_for_read_only_with_prefix(&db_dir, 2)?;
+    let keys = |prefix: &[u8]| -> Result<Vec<Vec<u8>>> {
+        let mut keys = vec![];
+        for item in db.prefix_iterator(prefix) {
+            keys.push(item?.0.into_vec());
+        }
+        Ok(keys)
+    };
+    assert_eq!(
+        keys(b"0a")?,
+        vec![
+            b"0a".to_vec(),
+            b"0a0".to_vec(),
+            b"0a1".to_vec(),
+            b"0aff".to_vec()
+        ]
+    );
+    assert_eq!(keys(b"0b")?, vec![b"0b".to_vec(), b"0b0".to_vec()]);
+    assert!(keys(b"0c")?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn num_keys_estimate_is_close_after_a_flush() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-open-num-keys")?;
+    let db_dir = dir.path().join("db").to_string_lossy().into_owned();
+    let num_keys = 10_000;
+    let db = open_rocksdb_for_write(&db_dir, WriteOpenOptions::default())?;
+    for i in 0..num_keys {
+        db.put(format!("{:08x}", i), b"value")?;
+    }
+    db.flush()?;
+
+    let estimate = collect_rocksdb_stats(&db)?.num_keys_estimate.unwrap_or(0);
+    // distinct keys without deletes or overwrites, so the estimate should be close to exact
+    assert!(
+        estimate.abs_diff(num_keys) <= num_keys / 10,
+        "estimate {} for {} keys",
+        estimate,
+        num_keys
+    );
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! `par_scan` and the forward and reverse range iterators of `parscan` on small temp DBs.
+
+use anyhow::Result;
+use rayon::prelude::*;
+use rocksdb_examples::parscan::{par_scan, range_iterator, range_iterator_reverse};
+use rocksdb_examples::rocksdb_utils::{
+    KeyRange, WriteOpenOptions, next_prefix, open_rocksdb_for_write,
+};
+use rocksdb_examples::utils::ScratchDir;
+use rust_rocksdb::{DBIterator, IteratorMode};
+
+fn keys(db_iter: DBIterator) -> Result<Vec<Vec<u8>>> {
+    let mut keys = vec![];
+    for item in db_iter {
+        keys.push(item?.0.into_vec());
+    }
+    Ok(keys)
+}
+
+#[test]
+fn reverse_sees_the_forward_keys_in_reverse() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-parscan-reverse")?;
+    let db_dir = dir.path().join("db");
+    let db = open_rocksdb_for_write(&db_dir.to_string_lossy(), WriteOpenOptions::default())?;
+    for key in ["0", "00", "0a", "0f", "0ff", "1", "10", "a", "ff"] {
+        db.put(key, b"")?;
+    }
+    // keys flushed to SST files and still in the memtable are merged the same way
+    db.flush()?;
+    db.put("0b", b"")?;
+
+    let ranges: Vec<KeyRange> = vec![
+        (None, None),
+        (Some(b"0".to_vec()), next_prefix(b"0")),
+        (Some(b"0a".to_vec()), Some(b"1".to_vec())),
+        (None, Some(b"0b".to_vec())),
+        (Some(b"a".to_vec()), None),
+        // empty ranges
+        (Some(b"2".to_vec()), Some(b"3".to_vec())),
+        (Some(b"fg".to_vec()), None),
+    ];
+    for (lower, upper) in ranges {
+        let forward = keys(range_iterator(&db, lower.clone(), upper.clone()))?;
+        let mut reverse = keys(range_iterator_reverse(&db, lower.clone(), upper.clone()))?;
+        reverse.reverse();
+        assert_eq!(forward, reverse, "range {:?}..{:?}", lower, upper);
+    }
+
+    let count = |lower: &[u8], upper: &[u8]| -> Result<usize> {
+        Ok(keys(range_iterator_reverse(
+            &db,
+            Some(lower.to_vec()),
+            Some(upper.to_vec()),
+        ))?
+        .len())
+    };
+    // 0, 00, 0a, 0b, 0f, 0ff
+    assert_eq!(count(b"0", b"1")?, 6);
+    assert_eq!(count(b"0a", b"0f")?, 2);
+    Ok(())
+}
+
+#[test]
+fn par_scan_counts_every_key_once() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-parscan-count")?;
+    let db_dir = dir.path().join("db");
+    let db = open_rocksdb_for_write(&db_dir.to_string_lossy(), WriteOpenOptions::default())?;
+    for i in 0..2_000 {
+        db.put(format!("{:06x}", i * 7_919 % 0x1000000), [b'v'; 32])?;
+    }
+    // keys outside the hex prefixes land in the unbounded first and last ranges
+    for key in ["", "~", "zz"] {
+        db.put(key, b"")?;
+    }
+    db.flush()?;
+    db.put("abc123", b"")?;
+
+    let serial = keys(db.full_iterator(IteratorMode::Start))?.len();
+    assert_eq!(serial, 2_004);
+    // more shards than keys are clamped to the number of prefixes, and some ranges are empty
+    for num_shards in [0, 1, 2, 7, 64, 4_096, 10_000] {
+        let count = par_scan(&db, num_shards)
+            .map(|item| item.map(|_| 1))
+            .sum::<Result<usize>>()?;
+        assert_eq!(count, serial, "{} shards", num_shards);
+    }
+    Ok(())
+}
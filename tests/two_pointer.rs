@@ -0,0 +1,230 @@
+//! `two_pointer_counts` on small temp DBs, including empty ones, its invariant check, and the balanced ranges
+//! two-pointer-parallel merges independently, with and without snapshots.
+
+use anyhow::Result;
+use rocksdb_examples::rocksdb_utils::{
+    KeyRange, TwoPointerCounts, WriteOpenOptions, open_rocksdb_for_write,
+    split_key_ranges_by_combined_size, split_key_ranges_by_size, two_pointer_counts,
+    two_pointer_counts_in_range,
+};
+use rocksdb_examples::utils::{ScratchDir, generate_consecutive_hex_strings};
+use rust_rocksdb::{DB, IteratorMode, Snapshot};
+
+fn open_with_keys(dir: &ScratchDir, name: &str, keys: &[&str]) -> Result<DB> {
+    let db_dir = dir.path().join(name);
+    let db = open_rocksdb_for_write(&db_dir.to_string_lossy(), WriteOpenOptions::default())?;
+    for key in keys {
+        db.put(key, b"")?;
+    }
+    Ok(db)
+}
+
+fn count(left: &DB, right: &DB) -> Result<TwoPointerCounts> {
+    let mut steps = 0;
+    let counts = two_pointer_counts(
+        left.full_iterator(IteratorMode::Start),
+        right.full_iterator(IteratorMode::Start),
+        || steps += 1,
+    )?;
+    counts.check()?;
+    // one step per distinct key across both sides
+    assert_eq!(steps, counts.left + counts.right - counts.intersection);
+    Ok(counts)
+}
+
+#[test]
+fn both_empty() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-two-pointer-both-empty")?;
+    let left = open_with_keys(&dir, "left", &[])?;
+    let right = open_with_keys(&dir, "right", &[])?;
+    assert_eq!(count(&left, &right)?, TwoPointerCounts::default());
+    Ok(())
+}
+
+#[test]
+fn one_side_empty() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-two-pointer-one-empty")?;
+    let empty = open_with_keys(&dir, "empty", &[])?;
+    let full = open_with_keys(&dir, "full", &["000", "0a1", "fff"])?;
+    assert_eq!(
+        count(&empty, &full)?,
+        TwoPointerCounts {
+            left: 0,
+            right: 3,
+            intersection: 0
+        }
+    );
+    assert_eq!(
+        count(&full, &empty)?,
+        TwoPointerCounts {
+            left: 3,
+            right: 0,
+            intersection: 0
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn overlapping() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-two-pointer-overlapping")?;
+    let left = open_with_keys(&dir, "left", &["001", "002", "00a", "fff"])?;
+    let right = open_with_keys(&dir, "right", &["000", "002", "00a", "00b"])?;
+    assert_eq!(
+        count(&left, &right)?,
+        TwoPointerCounts {
+            left: 4,
+            right: 4,
+            intersection: 2
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn check_rejects_intersection_above_a_total() {
+    let counts = |left, right, intersection| TwoPointerCounts {
+        left,
+        right,
+        intersection,
+    };
+    assert!(counts(0, 0, 0).check().is_ok());
+    assert!(counts(3, 5, 3).check().is_ok());
+    assert!(counts(3, 5, 4).check().is_err());
+    assert!(counts(5, 2, 3).check().is_err());
+}
+
+/// Keys with a hot "00" prefix like write-skewed's, plus a few outside the hex prefixes, flushed to an SST file.
+fn open_skewed(dir: &ScratchDir, name: &str, step: usize) -> Result<DB> {
+    let db_dir = dir.path().join(name);
+    let db = open_rocksdb_for_write(&db_dir.to_string_lossy(), WriteOpenOptions::default())?;
+    for i in (0..8_000).step_by(step) {
+        // 80% of the keys are 00xxxx
+        let key = if i % 5 == 0 {
+            format!("{:06x}", i * 1_999 % 0x1000000)
+        } else {
+            format!("00{:04x}", i * 8)
+        };
+        db.put(key, [b'v'; 32])?;
+    }
+    for key in ["", "~", "zz"] {
+        db.put(key, b"")?;
+    }
+    db.flush()?;
+    Ok(db)
+}
+
+/// Keys spread evenly over the hex keyspace, flushed to an SST file.
+fn open_uniform(dir: &ScratchDir, name: &str) -> Result<DB> {
+    let db_dir = dir.path().join(name);
+    let db = open_rocksdb_for_write(&db_dir.to_string_lossy(), WriteOpenOptions::default())?;
+    for i in 0..8_000 {
+        db.put(format!("{:06x}", i * 2_000), [b'v'; 32])?;
+    }
+    db.flush()?;
+    Ok(db)
+}
+
+/// Merge each range separately like two-pointer-parallel, reading from `snapshots` if given, returning the summed
+/// counts and the keys read per range.
+fn count_ranges(
+    left: &DB,
+    right: &DB,
+    ranges: &[KeyRange],
+    snapshots: Option<(&Snapshot, &Snapshot)>,
+) -> Result<(TwoPointerCounts, Vec<usize>)> {
+    let mut totals = TwoPointerCounts::default();
+    let mut range_keys = vec![];
+    for range in ranges {
+        let counts = two_pointer_counts_in_range(left, right, range, snapshots)?;
+        totals += counts;
+        range_keys.push(counts.left + counts.right);
+    }
+    Ok((totals, range_keys))
+}
+
+#[test]
+fn balanced_ranges_match_the_serial_merge() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-two-pointer-balanced")?;
+    let left = open_skewed(&dir, "left", 1)?;
+    let right = open_skewed(&dir, "right", 3)?;
+    let serial = count(&left, &right)?;
+
+    let num_ranges = 8;
+    let ranges = split_key_ranges_by_size(&left, &generate_consecutive_hex_strings(3), num_ranges);
+    assert!(ranges.len() > 1 && ranges.len() <= num_ranges);
+    let (totals, range_keys) = count_ranges(&left, &right, &ranges, None)?;
+    assert_eq!(totals, serial);
+
+    // a fixed shard per first hex digit puts the hot prefix's 80% in one shard, the balanced ranges split it
+    let total = serial.left + serial.right;
+    let largest = *range_keys.iter().max().unwrap();
+    assert!(
+        largest * 2 < total,
+        "keys per range {:?} of {}",
+        range_keys,
+        total
+    );
+    Ok(())
+}
+
+#[test]
+fn combined_ranges_split_a_prefix_hot_in_the_smaller_db() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-two-pointer-combined")?;
+    let left = open_uniform(&dir, "left")?;
+    let right = open_skewed(&dir, "right", 2)?;
+    let serial = count(&left, &right)?;
+    let total = serial.left + serial.right;
+    let prefixes = generate_consecutive_hex_strings(3);
+    let num_ranges = 8;
+
+    // split by the larger DB alone, the smaller one's hot prefix stays in one range
+    let ranges = split_key_ranges_by_size(&left, &prefixes, num_ranges);
+    let (totals, range_keys) = count_ranges(&left, &right, &ranges, None)?;
+    assert_eq!(totals, serial);
+    let largest = *range_keys.iter().max().unwrap();
+    assert!(
+        largest * 4 > total,
+        "keys per range {:?} of {}",
+        range_keys,
+        total
+    );
+
+    let ranges = split_key_ranges_by_combined_size(&[&left, &right], &prefixes, num_ranges);
+    let (totals, range_keys) = count_ranges(&left, &right, &ranges, None)?;
+    assert_eq!(totals, serial);
+    let largest = *range_keys.iter().max().unwrap();
+    assert!(
+        largest * 4 < total,
+        "keys per range {:?} of {}",
+        range_keys,
+        total
+    );
+    Ok(())
+}
+
+#[test]
+fn snapshot_ranges_ignore_later_writes() -> Result<()> {
+    let dir = ScratchDir::create(None, "test-two-pointer-snapshot")?;
+    let left = open_skewed(&dir, "left", 1)?;
+    let right = open_skewed(&dir, "right", 3)?;
+    let ranges = split_key_ranges_by_size(&left, &generate_consecutive_hex_strings(3), 4);
+
+    // on a static DB, reading from snapshots doesn't change the counts
+    let before = count(&left, &right)?;
+    let (snapshot_left, snapshot_right) = (left.snapshot(), right.snapshot());
+    let snapshots = Some((&snapshot_left, &snapshot_right));
+    assert_eq!(count_ranges(&left, &right, &ranges, None)?.0, before);
+    assert_eq!(count_ranges(&left, &right, &ranges, snapshots)?.0, before);
+
+    // writes after the snapshots, in the hot prefix, a cold one and outside the hex prefixes
+    for key in ["000001", "fff", "~~"] {
+        left.put(key, b"")?;
+    }
+    right.put("000001", b"")?;
+    let after = count(&left, &right)?;
+    assert_ne!(after, before);
+    assert_eq!(count_ranges(&left, &right, &ranges, None)?.0, after);
+    assert_eq!(count_ranges(&left, &right, &ranges, snapshots)?.0, before);
+    Ok(())
+}